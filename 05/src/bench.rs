@@ -0,0 +1,132 @@
+use crate::{Crane, CrateMover9000, CrateMover9001, Move, Piles};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+// The original crate-at-a-time implementation, kept to compare against
+#[derive(Clone)]
+struct DequePiles {
+    piles: HashMap<usize, VecDeque<char>>,
+}
+
+impl DequePiles {
+    fn from_piles(piles: &Piles) -> DequePiles {
+        DequePiles {
+            piles: piles
+                .piles
+                .iter()
+                .map(|(k, p)| (*k, p.iter().copied().collect()))
+                .collect(),
+        }
+    }
+
+    fn do_move(&mut self, move_: &Move) {
+        for _x in 0..move_.num {
+            let pile = self.piles.get_mut(&move_.from);
+            if let Some(pile) = pile {
+                let c = pile.pop_back().unwrap();
+                if let Some(pile) = self.piles.get_mut(&move_.to) {
+                    pile.push_back(c);
+                }
+            }
+        }
+    }
+
+    fn do_move_9001(&mut self, move_: &Move) {
+        let pile = self.piles.get_mut(&move_.from).unwrap();
+        let mut tmp_queue: VecDeque<char> = VecDeque::new();
+        for _x in 0..move_.num {
+            let c = pile.pop_back().unwrap();
+            tmp_queue.push_front(c);
+        }
+        let pile = self.piles.get_mut(&move_.to).unwrap();
+        for _x in 0..move_.num {
+            let c = tmp_queue.pop_front().unwrap();
+            pile.push_back(c);
+        }
+    }
+
+    fn tops(&self) -> String {
+        (1..=self.piles.len())
+            .filter_map(|x| self.piles.get(&x).and_then(|p| p.back()))
+            .collect()
+    }
+}
+
+type DequeMove = fn(&mut DequePiles, &Move);
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+// Generates a valid procedure over `pile_count` piles holding `crates` crates in total
+fn generate(pile_count: usize, crates: usize, moves: usize) -> (Piles, Vec<Move>) {
+    let mut rng = XorShift(0x2022_0005);
+    let mut piles = Piles::new();
+    for pile in 1..=pile_count {
+        piles.piles.insert(pile, Vec::new());
+    }
+    for _x in 0..crates {
+        let pile = rng.next(pile_count) + 1;
+        let c = (b'A' + rng.next(26) as u8) as char;
+        piles.piles.get_mut(&pile).unwrap().push(c);
+    }
+
+    let mut heights: Vec<usize> = (1..=pile_count).map(|p| piles.piles[&p].len()).collect();
+    let mut procedure = Vec::new();
+    while procedure.len() < moves {
+        let from = rng.next(pile_count);
+        let to = rng.next(pile_count);
+        if from == to || heights[from] == 0 {
+            continue;
+        }
+        let num = rng.next(heights[from]) + 1;
+        heights[from] -= num;
+        heights[to] += num;
+        procedure.push(Move {
+            num,
+            from: from + 1,
+            to: to + 1,
+        });
+    }
+    (piles, procedure)
+}
+
+fn time<F: FnOnce() -> String>(f: F) -> (Duration, String) {
+    let start = Instant::now();
+    let tops = f();
+    (start.elapsed(), tops)
+}
+
+pub fn run(crates: usize, moves: usize) {
+    let (start, procedure) = generate(9, crates, moves);
+    let deque_start = DequePiles::from_piles(&start);
+    println!("Benchmark: {} crates, {} moves", crates, moves);
+
+    let cranes: [(&dyn Crane, DequeMove); 2] = [
+        (&CrateMover9000, DequePiles::do_move),
+        (&CrateMover9001, DequePiles::do_move_9001),
+    ];
+    for (crane, deque_move) in cranes {
+        let (deque_time, deque_tops) = time(|| {
+            let mut piles = deque_start.clone();
+            procedure.iter().for_each(|m| deque_move(&mut piles, m));
+            piles.tops()
+        });
+        let (split_time, split_tops) = time(|| {
+            let mut piles = start.clone();
+            procedure.iter().for_each(|m| crane.do_move(&mut piles, m));
+            piles.tops()
+        });
+        assert_eq!(deque_tops, split_tops);
+        println!("{}:", crane.name());
+        println!("\tVecDeque, one at a time: {:?}", deque_time);
+        println!("\tVec, split_off/extend:   {:?}", split_time);
+    }
+}
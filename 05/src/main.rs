@@ -1,148 +1,645 @@
 use regex::Regex;
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::thread;
+use std::time::Duration;
 
-#[derive(Debug)]
+mod bench;
+
+#[derive(Debug, Clone, PartialEq)]
 struct Move {
     num: usize,
     from: usize,
     to: usize,
 }
 
-#[derive(Debug)]
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.num, self.from, self.to)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 struct Piles {
-    piles: HashMap<usize, VecDeque<char>>,
+    piles: HashMap<usize, Vec<char>>,
 }
 impl Piles {
+    // Crates are read top-down, so piles are built upside down and
+    // flipped by `settle` once the drawing has been parsed
     fn pile_on(&mut self, pile: usize, c: char) {
+        self.piles.entry(pile).or_default().push(c);
+    }
+
+    fn settle(&mut self) {
+        self.piles.values_mut().for_each(|p| p.reverse());
+    }
+
+    fn take(&mut self, from: usize, count: usize) -> Vec<char> {
+        let pile = self.piles.get_mut(&from).unwrap();
+        pile.split_off(pile.len() - count)
+    }
+
+    fn lift(&mut self, from: usize, to: usize, count: usize) {
+        let crates = self.take(from, count);
+        self.piles.get_mut(&to).unwrap().extend(crates);
+    }
+
+    fn lift_reversed(&mut self, from: usize, to: usize, count: usize) {
+        let crates = self.take(from, count);
         self.piles
-            .entry(pile)
-            .or_insert(VecDeque::new())
-            .push_front(c);
-    }
-
-    fn do_move(&mut self, move_: &Move) {
-        // println!("Move: {:?}", move_);
-        for _x in 0..move_.num {
-            let pile = self.piles.get_mut(&move_.from);
-            if let Some(pile) = pile {
-                let c = pile.pop_back().unwrap();
-                // println!("move 1 from {} to {}", move_.from, move_.to);
-                if let Some(pile) = self.piles.get_mut(&move_.to) {
-                    pile.push_back(c);
-                }
+            .get_mut(&to)
+            .unwrap()
+            .extend(crates.into_iter().rev());
+    }
+
+    fn validate(&self, index: usize, move_: &Move) -> Result<(), MoveError> {
+        check_move(index, move_, |p| self.piles.get(&p).map(|p| p.len()))
+    }
+
+    fn tops(&self) -> String {
+        (1..=self.piles.len())
+            .filter_map(|x| self.piles.get(&x).and_then(|p| p.last()))
+            .collect()
+    }
+
+    fn new() -> Piles {
+        Piles {
+            piles: HashMap::new(),
+        }
+    }
+}
+impl fmt::Display for Piles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let height = self.piles.values().map(|p| p.len()).max().unwrap_or(0);
+        for level in (0..height).rev() {
+            let row = (1..=self.piles.len())
+                .map(|x| match self.piles.get(&x).and_then(|p| p.get(level)) {
+                    Some(c) => format!("[{}]", c),
+                    None => "   ".to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(" ");
+            writeln!(f, "{}", row.trim_end())?;
+        }
+        let nums = (1..=self.piles.len())
+            .map(|x| format!(" {} ", x))
+            .collect::<Vec<String>>()
+            .join(" ");
+        write!(f, "{}", nums)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum MoveError {
+    MissingPile {
+        index: usize,
+        pile: usize,
+    },
+    Shortfall {
+        index: usize,
+        pile: usize,
+        needed: usize,
+        available: usize,
+    },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::MissingPile { index, pile } => {
+                write!(f, "move #{}: pile {} does not exist", index + 1, pile)
             }
+            MoveError::Shortfall {
+                index,
+                pile,
+                needed,
+                available,
+            } => write!(
+                f,
+                "move #{}: pile {} has {} crates but {} are needed ({} short)",
+                index + 1,
+                pile,
+                available,
+                needed,
+                needed - available
+            ),
         }
     }
+}
+
+fn check_move(
+    index: usize,
+    move_: &Move,
+    height: impl Fn(usize) -> Option<usize>,
+) -> Result<(), MoveError> {
+    if height(move_.to).is_none() {
+        return Err(MoveError::MissingPile {
+            index,
+            pile: move_.to,
+        });
+    }
+    let available = height(move_.from).ok_or(MoveError::MissingPile {
+        index,
+        pile: move_.from,
+    })?;
+    if available < move_.num {
+        return Err(MoveError::Shortfall {
+            index,
+            pile: move_.from,
+            needed: move_.num,
+            available,
+        });
+    }
+    Ok(())
+}
+
+// Checks the whole procedure against the pile heights without moving any crates
+fn dry_run(piles: &Piles, moves: &[Move]) -> Result<(), MoveError> {
+    let mut heights: HashMap<usize, usize> =
+        piles.piles.iter().map(|(k, p)| (*k, p.len())).collect();
+    for (index, move_) in moves.iter().enumerate() {
+        check_move(index, move_, |p| heights.get(&p).copied())?;
+        *heights.get_mut(&move_.from).unwrap() -= move_.num;
+        *heights.get_mut(&move_.to).unwrap() += move_.num;
+    }
+    Ok(())
+}
 
-    fn do_move_9001(&mut self, move_: &Move) {
-        // println!("Move: {:?}", move_);
-        let pile = self.piles.get_mut(&move_.from).unwrap();
-        let mut tmp_queue: VecDeque<char> = VecDeque::new();
-        for _x in 0..move_.num {
-            let c = pile.pop_back().unwrap();
-            tmp_queue.push_front(c);
+trait Crane {
+    fn name(&self) -> String;
+    fn do_move(&self, piles: &mut Piles, move_: &Move);
+    fn undo_move(&self, piles: &mut Piles, move_: &Move);
+}
+
+struct CrateMover9000;
+struct CrateMover9001;
+
+// Lifts at most `capacity` crates at a time, keeping their order within a batch
+struct LimitedCrane {
+    capacity: usize,
+}
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        "CrateMover 9000".to_string()
+    }
+
+    fn do_move(&self, piles: &mut Piles, move_: &Move) {
+        piles.lift_reversed(move_.from, move_.to, move_.num);
+    }
+
+    fn undo_move(&self, piles: &mut Piles, move_: &Move) {
+        piles.lift_reversed(move_.to, move_.from, move_.num);
+    }
+}
+
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        "CrateMover 9001".to_string()
+    }
+
+    fn do_move(&self, piles: &mut Piles, move_: &Move) {
+        piles.lift(move_.from, move_.to, move_.num);
+    }
+
+    fn undo_move(&self, piles: &mut Piles, move_: &Move) {
+        piles.lift(move_.to, move_.from, move_.num);
+    }
+}
+
+impl Crane for LimitedCrane {
+    fn name(&self) -> String {
+        format!("Limited crane ({} per lift)", self.capacity)
+    }
+
+    fn do_move(&self, piles: &mut Piles, move_: &Move) {
+        let mut remaining = move_.num;
+        while remaining > 0 {
+            let batch = std::cmp::min(self.capacity, remaining);
+            piles.lift(move_.from, move_.to, batch);
+            remaining -= batch;
+        }
+    }
+
+    fn undo_move(&self, piles: &mut Piles, move_: &Move) {
+        // The short batch was lifted last, so it goes back first
+        let mut remaining = move_.num;
+        let partial = move_.num % self.capacity;
+        if partial > 0 {
+            piles.lift(move_.to, move_.from, partial);
+            remaining -= partial;
         }
-        let pile = self.piles.get_mut(&move_.to).unwrap();
-        for _x in 0..move_.num {
-            let c = tmp_queue.pop_front().unwrap();
-            pile.push_back(c);
+        while remaining > 0 {
+            piles.lift(move_.to, move_.from, self.capacity);
+            remaining -= self.capacity;
         }
     }
+}
 
-    fn print_back(&self) {
-        for x in 1..=self.piles.len() {
-            let c = self.piles.get(&x).unwrap().back().unwrap();
-            println!("{}", c);
+fn parse_crane(spec: &str) -> Option<Box<dyn Crane>> {
+    match spec {
+        "9000" => Some(Box::new(CrateMover9000)),
+        "9001" => Some(Box::new(CrateMover9001)),
+        _ => {
+            let capacity = spec.strip_prefix("limited:")?.parse::<usize>().ok()?;
+            if capacity == 0 {
+                return None;
+            }
+            Some(Box::new(LimitedCrane { capacity }))
         }
     }
+}
 
-    fn new() -> Piles {
-        Piles {
-            piles: HashMap::new(),
+struct Step {
+    index: usize,
+    move_: Move,
+    before: Piles,
+    after: Piles,
+}
+
+struct Trace {
+    steps: Vec<Step>,
+    delay: Option<Duration>,
+}
+
+impl Trace {
+    fn new(delay: Option<Duration>) -> Trace {
+        Trace {
+            steps: Vec::new(),
+            delay,
+        }
+    }
+
+    fn record(&mut self, piles: &mut Piles, move_: &Move, crane: &dyn Crane) {
+        let before = piles.clone();
+        crane.do_move(piles, move_);
+        let step = Step {
+            index: self.steps.len(),
+            move_: move_.clone(),
+            before,
+            after: piles.clone(),
+        };
+        if let Some(delay) = self.delay {
+            animate(&step, delay);
         }
+        self.steps.push(step);
     }
+
+    fn print(&self) {
+        for step in self.steps.iter() {
+            println!("#{} {}", step.index + 1, step.move_);
+            println!("{}", step.before);
+            println!("->");
+            println!("{}", step.after);
+            println!();
+        }
+    }
+}
+
+fn animate(step: &Step, delay: Duration) {
+    // Clear the terminal and move the cursor home before each frame
+    print!("\x1b[2J\x1b[H");
+    println!("#{} {}", step.index + 1, step.move_);
+    println!();
+    println!("{}", step.after);
+    thread::sleep(delay);
 }
+
 enum ParseState {
     Crates,
     Nums,
     Moves,
 }
 
-fn parse_crates(piles: &mut Piles, line: &String) -> ParseState {
+fn parse_crates(piles: &mut Piles, line: &str) -> ParseState {
     for (index, c) in line.chars().enumerate() {
         if c.is_ascii_uppercase() {
             let pile = (index / 4) + 1;
             piles.pile_on(pile, c);
         }
         if c.is_numeric() {
+            line.split_ascii_whitespace()
+                .filter_map(|n| n.parse::<usize>().ok())
+                .for_each(|n| {
+                    piles.piles.entry(n).or_default();
+                });
+            piles.settle();
             return ParseState::Nums;
         }
     }
     ParseState::Crates
 }
 
-fn parse_move(line: &str) -> Result<Move, &'static str> {
-    let re = Regex::new(r"move (\d*) from (\d*) to (\d*)").unwrap();
-    if let Some(caps) = re.captures(line) {
-        let num = caps[1].parse::<usize>().unwrap();
-        let from = caps[2].parse::<usize>().unwrap();
-        let to = caps[3].parse::<usize>().unwrap();
+fn parse_move(line: &str) -> Option<Move> {
+    let re = Regex::new(r"move (\d+) from (\d+) to (\d+)").unwrap();
+    let caps = re.captures(line)?;
+    Some(Move {
+        num: caps[1].parse().ok()?,
+        from: caps[2].parse().ok()?,
+        to: caps[3].parse().ok()?,
+    })
+}
 
-        return Ok(Move { num, from, to });
-    }
-    Err("Failed to parse line")
+// A move line that could not be read, numbered from 1 as in the file
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    line: usize,
+    content: String,
 }
 
-fn part1(filename: &str) {
-    let file = File::open(filename).unwrap();
-    let reader = BufReader::new(file);
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}: cannot parse move {:?}",
+            self.line, self.content
+        )
+    }
+}
 
+fn parse_input(reader: impl BufRead) -> Result<(Piles, Vec<Move>), ParseError> {
     let mut piles = Piles::new();
+    let mut moves = Vec::new();
 
     let mut parse_state = ParseState::Crates;
 
-    for line in reader.lines().flatten() {
-        match parse_state {
-            ParseState::Crates => parse_state = parse_crates(&mut piles, &line),
-            ParseState::Nums => parse_state = ParseState::Moves,
+    for (index, line) in reader.lines().map_while(Result::ok).enumerate() {
+        parse_state = match parse_state {
+            ParseState::Crates => parse_crates(&mut piles, &line),
+            ParseState::Nums => ParseState::Moves,
+            ParseState::Moves if line.trim().is_empty() => ParseState::Moves,
             ParseState::Moves => {
-                let move_ = parse_move(&line).unwrap();
-                piles.do_move(&move_);
+                let move_ = parse_move(&line).ok_or(ParseError {
+                    line: index + 1,
+                    content: line.clone(),
+                })?;
+                moves.push(move_);
+                ParseState::Moves
             }
-        }
+        };
     }
-    println!("Part1:");
-    println!("{:?}", piles);
-    piles.print_back();
+    Ok((piles, moves))
 }
 
-fn part2(filename: &str) {
+fn read_input(filename: &str) -> Result<(Piles, Vec<Move>), ParseError> {
     let file = File::open(filename).unwrap();
-    let reader = BufReader::new(file);
+    parse_input(BufReader::new(file))
+}
 
-    let mut piles = Piles::new();
+fn simulate(
+    piles: &mut Piles,
+    moves: &[Move],
+    crane: &dyn Crane,
+    trace: Option<&mut Trace>,
+) -> Result<(), MoveError> {
+    let mut trace = trace;
+    for (index, move_) in moves.iter().enumerate() {
+        piles.validate(index, move_)?;
+        match trace.as_deref_mut() {
+            Some(trace) => trace.record(piles, move_, crane),
+            None => crane.do_move(piles, move_),
+        }
+    }
+    Ok(())
+}
 
-    let mut parse_state = ParseState::Crates;
+// Undoes the procedure backwards from a final arrangement
+fn reverse_simulate(piles: &mut Piles, moves: &[Move], crane: &dyn Crane) -> Result<(), MoveError> {
+    for (index, move_) in moves.iter().enumerate().rev() {
+        let inverse = Move {
+            num: move_.num,
+            from: move_.to,
+            to: move_.from,
+        };
+        piles.validate(index, &inverse)?;
+        crane.undo_move(piles, move_);
+    }
+    Ok(())
+}
 
-    for line in reader.lines().flatten() {
-        parse_state = match parse_state {
-            ParseState::Crates => parse_crates(&mut piles, &line),
-            ParseState::Nums => ParseState::Moves,
-            ParseState::Moves => {
-                let move_ = parse_move(&line).unwrap();
-                piles.do_move_9001(&move_);
-                ParseState::Moves
+// Recovers the starting arrangement and checks that it replays into `end`
+fn reconstruct(end: &Piles, moves: &[Move], crane: &dyn Crane) -> Result<(Piles, bool), MoveError> {
+    let mut start = end.clone();
+    reverse_simulate(&mut start, moves, crane)?;
+    let mut replay = start.clone();
+    simulate(&mut replay, moves, crane, None)?;
+    Ok((start, replay == *end))
+}
+
+// Usage: aoc-05 [--bench [crates] [moves]] [--check] [--reverse] [--trace] [--animate <ms>] [--crane 9000|9001|limited:<n>]... [filename]
+fn main() {
+    let mut filename = "input-example.txt".to_string();
+    let mut tracing = false;
+    let mut checking = false;
+    let mut reversing = false;
+    let mut delay = None;
+    let mut cranes: Vec<Box<dyn Crane>> = Vec::new();
+
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bench" => {
+                let crates = args
+                    .next()
+                    .and_then(|a| a.parse().ok())
+                    .unwrap_or(1_000_000);
+                let moves = args.next().and_then(|a| a.parse().ok()).unwrap_or(1_000);
+                bench::run(crates, moves);
+                return;
             }
+            "--check" => checking = true,
+            "--reverse" => reversing = true,
+            "--trace" => tracing = true,
+            "--animate" => {
+                let ms = args
+                    .next_if(|a| a.parse::<u64>().is_ok())
+                    .and_then(|a| a.parse().ok())
+                    .unwrap_or(500);
+                delay = Some(Duration::from_millis(ms));
+                tracing = true;
+            }
+            "--crane" => {
+                let spec = args.next().unwrap_or_default();
+                match parse_crane(&spec) {
+                    Some(crane) => cranes.push(crane),
+                    None => {
+                        eprintln!("Unknown crane: {}", spec);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            _ => filename = arg,
+        }
+    }
+
+    if cranes.is_empty() {
+        cranes.push(Box::new(CrateMover9000));
+        cranes.push(Box::new(CrateMover9001));
+    }
+
+    let (start, moves) = match read_input(&filename) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("{}: {}", filename, e);
+            std::process::exit(1);
+        }
+    };
+
+    if checking {
+        match dry_run(&start, &moves) {
+            Ok(()) => println!("All {} moves are valid", moves.len()),
+            Err(e) => {
+                println!("Invalid procedure: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if reversing {
+        // The drawing in the input is taken as the final arrangement
+        for crane in cranes.iter() {
+            match reconstruct(&start, &moves, crane.as_ref()) {
+                Ok((recovered, verified)) => {
+                    println!("{}:", crane.name());
+                    println!("{}", recovered);
+                    println!("Replays into final state: {}", verified);
+                }
+                Err(e) => {
+                    eprintln!("{}: {}", crane.name(), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        return;
+    }
+
+    for crane in cranes.iter() {
+        let mut piles = start.clone();
+        let mut trace = Trace::new(delay);
+        let result = if tracing {
+            simulate(&mut piles, &moves, crane.as_ref(), Some(&mut trace))
+        } else {
+            simulate(&mut piles, &moves, crane.as_ref(), None)
         };
+        if let Err(e) = result {
+            eprintln!("{}: {}", crane.name(), e);
+            std::process::exit(1);
+        }
+        if tracing && delay.is_none() {
+            trace.print();
+        }
+        println!("{}:", crane.name());
+        println!("{:?}", piles);
+        println!("Top crates: {}", piles.tops());
     }
-    println!("Part2:");
-    println!("{:?}", piles);
-    piles.print_back();
 }
-fn main() {
-    let filename = "input-example.txt";
-    part1(filename);
-    part2(filename);
+
+#[test]
+fn test_cranes() {
+    let (start, moves) = read_input("input-example.txt").unwrap();
+
+    let mut piles = start.clone();
+    simulate(&mut piles, &moves, &CrateMover9000, None).unwrap();
+    assert_eq!(piles.tops(), "CMZ");
+
+    let mut piles = start.clone();
+    simulate(&mut piles, &moves, &CrateMover9001, None).unwrap();
+    assert_eq!(piles.tops(), "MCD");
+
+    let mut piles = start.clone();
+    simulate(&mut piles, &moves, &LimitedCrane { capacity: 1 }, None).unwrap();
+    assert_eq!(piles.tops(), "CMZ");
+
+    let mut piles = start.clone();
+    simulate(&mut piles, &moves, &LimitedCrane { capacity: 2 }, None).unwrap();
+    assert_eq!(piles.tops(), "MCZ");
+}
+
+#[test]
+fn test_trace() {
+    let (start, moves) = read_input("input-example.txt").unwrap();
+    let mut trace = Trace::new(None);
+    let mut piles = start.clone();
+    simulate(&mut piles, &moves, &CrateMover9001, Some(&mut trace)).unwrap();
+    assert_eq!(trace.steps.len(), moves.len());
+
+    // Replay the moves independently and compare every snapshot
+    let mut expected = start.clone();
+    for (i, (step, move_)) in trace.steps.iter().zip(moves.iter()).enumerate() {
+        assert_eq!(step.index, i);
+        assert_eq!(&step.move_, move_);
+        assert_eq!(step.before, expected);
+        CrateMover9001.do_move(&mut expected, move_);
+        assert_eq!(step.after, expected);
+    }
+    assert_eq!(trace.steps.last().unwrap().after, piles);
+}
+
+#[test]
+fn test_invalid_moves() {
+    let (start, mut moves) = read_input("input-example.txt").unwrap();
+    assert_eq!(dry_run(&start, &moves), Ok(()));
+
+    moves.push(Move {
+        num: 4,
+        from: 1,
+        to: 2,
+    });
+    let shortfall = MoveError::Shortfall {
+        index: 4,
+        pile: 1,
+        needed: 4,
+        available: 1,
+    };
+    assert_eq!(dry_run(&start, &moves).as_ref(), Err(&shortfall));
+
+    let mut piles = start.clone();
+    assert_eq!(
+        simulate(&mut piles, &moves, &CrateMover9001, None),
+        Err(shortfall)
+    );
+
+    moves[4] = Move {
+        num: 1,
+        from: 7,
+        to: 2,
+    };
+    let missing = MoveError::MissingPile { index: 4, pile: 7 };
+    assert_eq!(dry_run(&start, &moves), Err(missing));
+    assert_eq!(
+        dry_run(&start, &moves).unwrap_err().to_string(),
+        "move #5: pile 7 does not exist"
+    );
+
+    let input = "[A]\n 1   2\n\nmove 1 from 1 to 2\nmove x from 2 to 1\n";
+    assert_eq!(
+        parse_input(input.as_bytes()).unwrap_err(),
+        ParseError {
+            line: 5,
+            content: "move x from 2 to 1".to_string()
+        }
+    );
+    let input = "[A]\n 1   2\n\nmove 99999999999999999999 from 1 to 2\n";
+    assert_eq!(parse_input(input.as_bytes()).unwrap_err().line, 4);
+}
+
+#[test]
+fn test_reconstruct() {
+    let (start, moves) = read_input("input-example.txt").unwrap();
+    let cranes: [&dyn Crane; 4] = [
+        &CrateMover9000,
+        &CrateMover9001,
+        &LimitedCrane { capacity: 2 },
+        &LimitedCrane { capacity: 3 },
+    ];
+    for crane in cranes {
+        let mut end = start.clone();
+        simulate(&mut end, &moves, crane, None).unwrap();
+        let (recovered, verified) = reconstruct(&end, &moves, crane).unwrap();
+        assert_eq!(recovered, start);
+        assert!(verified);
+    }
 }
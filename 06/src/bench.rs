@@ -0,0 +1,52 @@
+use crate::find_marker;
+use std::collections::HashSet;
+use std::time::Instant;
+
+// The original set-per-position implementation, kept to compare against
+#[allow(clippy::char_indices_as_byte_indices)]
+fn find_marker_hashset(line: &str, distinct: usize) -> usize {
+    let mut set = HashSet::new();
+
+    for (index, _c) in line.chars().enumerate() {
+        set.clear();
+
+        let (_left, right) = line.split_at(index);
+        right.chars().take(distinct).for_each(|d| {
+            set.insert(d);
+        });
+
+        if set.len() == distinct {
+            return index + set.len();
+        }
+    }
+    0
+}
+
+// A stream cycling through 3 symbols, so neither marker shows up until
+// the distinct symbols placed at the very end
+fn generate(megabytes: usize) -> String {
+    let len = megabytes * 1024 * 1024;
+    let mut stream: String = "abc".chars().cycle().take(len).collect();
+    stream.push_str("defghijklmnopq");
+    stream
+}
+
+pub fn run(megabytes: usize) {
+    let stream = generate(megabytes);
+    println!("Benchmark: {} bytes", stream.len());
+
+    for distinct in [4, 14] {
+        let start = Instant::now();
+        let old = find_marker_hashset(&stream, distinct);
+        let old_time = start.elapsed();
+
+        let start = Instant::now();
+        let new = find_marker(&stream, distinct).unwrap();
+        let new_time = start.elapsed();
+
+        assert_eq!(old, new);
+        println!("Window of {} (marker at {}):", distinct, new);
+        println!("\tHashSet per position: {:?}", old_time);
+        println!("\tSliding window:       {:?}", new_time);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{boundaries, markers_in, slice_symbols, Mode};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkerDef {
+    pub name: String,
+    pub distinct: usize,
+}
+
+// One framed segment: the marker that opened it and the payload running
+// up to the start of the next segment, or the end of the stream
+#[derive(Debug, PartialEq)]
+pub struct Segment {
+    pub kind: String,
+    pub start: usize,
+    pub end: usize,
+    pub marker: String,
+    pub payload: String,
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}..{}\t{}\t{}",
+            self.kind, self.start, self.end, self.marker, self.payload
+        )
+    }
+}
+
+// Parses definitions such as "packet=4,message=14"
+pub fn parse_definitions(spec: &str) -> Option<Vec<MarkerDef>> {
+    spec.split(',')
+        .map(|def| {
+            let (name, distinct) = def.split_once('=')?;
+            let distinct = distinct.trim().parse::<usize>().ok()?;
+            if name.trim().is_empty() || distinct == 0 {
+                return None;
+            }
+            Some(MarkerDef {
+                name: name.trim().to_string(),
+                distinct,
+            })
+        })
+        .collect()
+}
+
+pub struct Decoder {
+    defs: Vec<MarkerDef>,
+    mode: Mode,
+}
+
+impl Decoder {
+    pub fn new(defs: Vec<MarkerDef>, mode: Mode) -> Decoder {
+        Decoder { defs, mode }
+    }
+
+    // Splits the stream at the first marker of every definition. Anything
+    // before the earliest marker is reported as a "preamble" segment.
+    pub fn decode(&self, stream: &str) -> Vec<Segment> {
+        // Offsets are counted in symbols of `mode`; `bounds` maps them to bytes
+        let bounds = boundaries(stream, self.mode);
+        let len = bounds.len() - 1;
+        let slice = |from: usize, to: usize| slice_symbols(stream, &bounds, self.mode, from, to);
+
+        let mut starts: Vec<(usize, &MarkerDef)> = self
+            .defs
+            .iter()
+            .filter_map(|def| {
+                markers_in(stream, def.distinct, self.mode)
+                    .next()
+                    .map(|start| (start, def))
+            })
+            .collect();
+        starts.sort_by_key(|(start, _def)| *start);
+
+        let mut segments = Vec::new();
+        let first = starts.first().map(|(start, _def)| *start).unwrap_or(len);
+        if first > 0 {
+            segments.push(Segment {
+                kind: "preamble".to_string(),
+                start: 0,
+                end: first,
+                marker: String::new(),
+                payload: slice(0, first),
+            });
+        }
+
+        for (index, (start, def)) in starts.iter().enumerate() {
+            let end = match starts.get(index + 1) {
+                Some((next, _def)) => *next,
+                None => len,
+            };
+            segments.push(Segment {
+                kind: def.name.clone(),
+                start: *start,
+                end,
+                marker: slice(start - def.distinct, *start),
+                payload: slice(*start, end),
+            });
+        }
+        segments
+    }
+}
@@ -1,49 +1,521 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, Read};
+use unicode_segmentation::UnicodeSegmentation;
 
-fn find_marker(line: &str, distinct: usize) -> usize {
-    let mut set = HashSet::new();
+mod bench;
+mod decoder;
 
-    for (index, _c) in line.chars().enumerate() {
-        set.clear();
+use decoder::Decoder;
 
-        let (_left, right) = line.split_at(index);
-        right.chars().take(distinct).for_each(|d| {
-            set.insert(d);
-        });
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Byte,
+    Char,
+    Grapheme,
+}
+
+impl Mode {
+    fn parse(s: &str) -> Option<Mode> {
+        match s {
+            "byte" => Some(Mode::Byte),
+            "char" => Some(Mode::Char),
+            "grapheme" => Some(Mode::Grapheme),
+            _ => None,
+        }
+    }
+}
+
+// A unit of the datastream along with how occurrences of it are counted.
+// Bytes use a flat array, wider symbols fall back to a map.
+trait Symbol: Clone {
+    type Counts;
+    fn new_counts() -> Self::Counts;
+    fn count<'a>(counts: &'a mut Self::Counts, symbol: &Self) -> &'a mut usize;
+}
+
+impl Symbol for u8 {
+    type Counts = [usize; 256];
+
+    fn new_counts() -> [usize; 256] {
+        [0; 256]
+    }
+
+    fn count<'a>(counts: &'a mut [usize; 256], symbol: &u8) -> &'a mut usize {
+        &mut counts[*symbol as usize]
+    }
+}
+
+impl Symbol for char {
+    type Counts = HashMap<char, usize>;
+
+    fn new_counts() -> HashMap<char, usize> {
+        HashMap::new()
+    }
+
+    fn count<'a>(counts: &'a mut HashMap<char, usize>, symbol: &char) -> &'a mut usize {
+        counts.entry(*symbol).or_insert(0)
+    }
+}
+
+// Graphemes borrow from the line, so the map can key on them directly
+impl<'s> Symbol for &'s str {
+    type Counts = HashMap<&'s str, usize>;
+
+    fn new_counts() -> HashMap<&'s str, usize> {
+        HashMap::new()
+    }
+
+    fn count<'a>(counts: &'a mut HashMap<&'s str, usize>, symbol: &&'s str) -> &'a mut usize {
+        counts.entry(*symbol).or_insert(0)
+    }
+}
+
+// Slides a window over the stream keeping a count per symbol, so each
+// position costs O(1) instead of rebuilding a set of the whole window
+struct MarkerDetector<T: Symbol = u8> {
+    distinct: usize,
+    counts: T::Counts,
+    window: VecDeque<T>,
+    unique: usize,
+    position: usize,
+}
 
-        if set.len() == distinct {
-            return index + set.len();
+impl<T: Symbol> MarkerDetector<T> {
+    fn new(distinct: usize) -> MarkerDetector<T> {
+        MarkerDetector {
+            distinct,
+            counts: T::new_counts(),
+            window: VecDeque::with_capacity(distinct + 1),
+            unique: 0,
+            position: 0,
         }
     }
-    0
+
+    // Feeds the next symbol, returning the offset just past it if the
+    // window now holds `distinct` unique symbols
+    fn push(&mut self, symbol: T) -> Option<usize> {
+        self.position += 1;
+        if self.distinct == 0 {
+            return None;
+        }
+
+        let count = T::count(&mut self.counts, &symbol);
+        if *count == 0 {
+            self.unique += 1;
+        }
+        *count += 1;
+        self.window.push_back(symbol);
+
+        if self.window.len() > self.distinct {
+            let old = self.window.pop_front().unwrap();
+            let count = T::count(&mut self.counts, &old);
+            *count -= 1;
+            if *count == 0 {
+                self.unique -= 1;
+            }
+        }
+
+        if self.unique == self.distinct {
+            return Some(self.position);
+        }
+        None
+    }
+}
+
+impl MarkerDetector<u8> {
+    fn window(&self) -> String {
+        let bytes: Vec<u8> = self.window.iter().copied().collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+fn scan<T: Symbol>(
+    symbols: impl Iterator<Item = T>,
+    distinct: usize,
+) -> impl Iterator<Item = usize> {
+    let mut detector = MarkerDetector::new(distinct);
+    symbols.filter_map(move |s| detector.push(s))
 }
+
+fn find_marker(line: &str, distinct: usize) -> Option<usize> {
+    markers(line, distinct).next()
+}
+
+// Every offset at which a window of `distinct` unique symbols completes
+fn markers(line: &str, distinct: usize) -> impl Iterator<Item = usize> + '_ {
+    scan(line.bytes(), distinct)
+}
+
+// Same as `markers`, with offsets counted in the symbols of `mode`
+fn markers_in(line: &str, distinct: usize, mode: Mode) -> Box<dyn Iterator<Item = usize> + '_> {
+    match mode {
+        Mode::Byte => Box::new(scan(line.bytes(), distinct)),
+        Mode::Char => Box::new(scan(line.chars(), distinct)),
+        Mode::Grapheme => Box::new(scan(line.graphemes(true), distinct)),
+    }
+}
+
+// Byte offset of every symbol boundary, so symbol offsets can slice `line`
+fn boundaries(line: &str, mode: Mode) -> Vec<usize> {
+    let mut bounds: Vec<usize> = match mode {
+        Mode::Byte => (0..line.len()).collect(),
+        Mode::Char => line.char_indices().map(|(i, _c)| i).collect(),
+        Mode::Grapheme => line.grapheme_indices(true).map(|(i, _g)| i).collect(),
+    };
+    bounds.push(line.len());
+    bounds
+}
+
+// Text of symbols `from..to`. Byte offsets may split a character, so byte
+// mode converts lossily like `MarkerDetector::window` does.
+fn slice_symbols(line: &str, bounds: &[usize], mode: Mode, from: usize, to: usize) -> String {
+    let range = bounds[from]..bounds[to];
+    match mode {
+        Mode::Byte => String::from_utf8_lossy(&line.as_bytes()[range]).into_owned(),
+        Mode::Char | Mode::Grapheme => line[range].to_string(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Marker {
+    distinct: usize,
+    offset: usize,
+    window: String,
+}
+
+// Reads the datastream in chunks and yields the first marker of each
+// window size as soon as it completes, without keeping the stream around.
+// With `every` set, detectors keep going and report each later marker too.
+// Line terminators are held back until a later byte shows they are not at
+// the end, so a trailing newline is ignored as in the other modes.
+struct MarkerStream<R: Read> {
+    reader: R,
+    detectors: Vec<MarkerDetector>,
+    every: bool,
+    found: VecDeque<Marker>,
+    pending: Vec<u8>,
+    buffer: Vec<u8>,
+    start: usize,
+    end: usize,
+}
+
+impl<R: Read> MarkerStream<R> {
+    fn new(reader: R, sizes: &[usize]) -> MarkerStream<R> {
+        MarkerStream {
+            reader,
+            detectors: sizes.iter().map(|d| MarkerDetector::new(*d)).collect(),
+            every: false,
+            found: VecDeque::new(),
+            pending: Vec::new(),
+            buffer: vec![0; 8192],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    fn every(mut self) -> MarkerStream<R> {
+        self.every = true;
+        self
+    }
+
+    fn feed(&mut self, b: u8) {
+        let found = &mut self.found;
+        let every = self.every;
+        self.detectors
+            .retain_mut(|detector| match detector.push(b) {
+                Some(offset) => {
+                    found.push_back(Marker {
+                        distinct: detector.distinct,
+                        offset,
+                        window: detector.window(),
+                    });
+                    every
+                }
+                None => true,
+            });
+    }
+}
+
+impl<R: Read> Iterator for MarkerStream<R> {
+    type Item = io::Result<Marker>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.found.is_empty() && !self.detectors.is_empty() {
+            if self.start == self.end {
+                match self.reader.read(&mut self.buffer) {
+                    Ok(0) => return None,
+                    Ok(n) => {
+                        self.start = 0;
+                        self.end = n;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            let b = self.buffer[self.start];
+            self.start += 1;
+            if b == b'\r' || b == b'\n' {
+                self.pending.push(b);
+                continue;
+            }
+            for p in std::mem::take(&mut self.pending) {
+                self.feed(p);
+            }
+            self.feed(b);
+        }
+        self.found.pop_front().map(Ok)
+    }
+}
+
+// In-memory counterpart of `MarkerStream` for the char and grapheme modes
+fn find_markers(line: &str, sizes: &[usize], every: bool, mode: Mode) -> Vec<Marker> {
+    let bounds = boundaries(line, mode);
+    let mut found = Vec::new();
+    for distinct in sizes.iter() {
+        let offsets = markers_in(line, *distinct, mode);
+        let offsets: Vec<usize> = if every {
+            offsets.collect()
+        } else {
+            offsets.take(1).collect()
+        };
+        found.extend(offsets.into_iter().map(|offset| Marker {
+            distinct: *distinct,
+            offset,
+            window: slice_symbols(line, &bounds, mode, offset - distinct, offset),
+        }));
+    }
+    found.sort_by_key(|m| m.offset);
+    found
+}
+
+fn marker_name(distinct: usize) -> &'static str {
+    match distinct {
+        4 => "packet",
+        _ => "message",
+    }
+}
+
+// Usage: aoc-06 [--bench [megabytes]] [--all] [--mode byte|char|grapheme]
+//               [--decode [name=size,...]] [filename | -]
 fn main() {
-    let filename = "input";
-    let file = File::open(filename).unwrap();
-    let mut reader = BufReader::new(file);
+    let mut filename = "input".to_string();
+    let mut every = false;
+    let mut mode = Mode::Byte;
+    let mut decode = None;
+
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bench" => {
+                let megabytes = args.next().and_then(|a| a.parse().ok()).unwrap_or(4);
+                bench::run(megabytes);
+                return;
+            }
+            "--all" => every = true,
+            "--mode" => {
+                let arg = args.next().unwrap_or_default();
+                match Mode::parse(&arg) {
+                    Some(m) => mode = m,
+                    None => {
+                        eprintln!("Unknown mode: {}", arg);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--decode" => {
+                let spec = args
+                    .next_if(|a| a.contains('='))
+                    .unwrap_or("packet=4,message=14".to_string());
+                match decoder::parse_definitions(&spec) {
+                    Some(defs) => decode = Some(defs),
+                    None => {
+                        eprintln!("Invalid marker definitions: {}", spec);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            _ => filename = arg,
+        }
+    }
 
-    let mut line = String::new();
-    let _r = reader.read_line(&mut line);
-    println!("Marker packet start at: {}", find_marker(&line, 4));
-    println!("Marker message start at: {}", find_marker(&line, 14));
+    let mut reader: Box<dyn Read> = if filename == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(File::open(&filename).unwrap())
+    };
+
+    if let Some(defs) = decode {
+        let mut stream = String::new();
+        reader.read_to_string(&mut stream).unwrap();
+        let stream = stream.trim_end_matches(['\r', '\n']);
+        println!("kind\trange\tmarker\tpayload");
+        for segment in Decoder::new(defs, mode).decode(stream) {
+            println!("{}", segment);
+        }
+        return;
+    }
+
+    let sizes = [4, 14];
+    let markers: Box<dyn Iterator<Item = io::Result<Marker>>> = if mode == Mode::Byte {
+        let stream = MarkerStream::new(reader, &sizes);
+        Box::new(if every { stream.every() } else { stream })
+    } else {
+        let mut line = String::new();
+        reader.read_to_string(&mut line).unwrap();
+        let line = line.trim_end_matches(['\r', '\n']);
+        Box::new(find_markers(line, &sizes, every, mode).into_iter().map(Ok))
+    };
+
+    let mut found = Vec::new();
+    for marker in markers {
+        let marker = marker.unwrap();
+        if every {
+            println!(
+                "{} marker at {}: {}",
+                marker_name(marker.distinct),
+                marker.offset,
+                marker.window
+            );
+        } else {
+            println!(
+                "Marker {} start at: {}",
+                marker_name(marker.distinct),
+                marker.offset
+            );
+        }
+        found.push(marker.distinct);
+    }
+
+    for distinct in sizes.iter().filter(|d| !found.contains(d)) {
+        println!("No {} marker found", marker_name(*distinct));
+    }
 }
 
 #[test]
 fn test1() {
-    assert_eq!(find_marker("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4), 7);
-    assert_eq!(find_marker("bvwbjplbgvbhsrlpgdmjqwftvncz", 4), 5);
-    assert_eq!(find_marker("nppdvjthqldpwncqszvftbrmjlhg", 4), 6);
-    assert_eq!(find_marker("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 4), 10);
-    assert_eq!(find_marker("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 4), 11);
+    assert_eq!(find_marker("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4), Some(7));
+    assert_eq!(find_marker("bvwbjplbgvbhsrlpgdmjqwftvncz", 4), Some(5));
+    assert_eq!(find_marker("nppdvjthqldpwncqszvftbrmjlhg", 4), Some(6));
+    assert_eq!(
+        find_marker("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 4),
+        Some(10)
+    );
+    assert_eq!(find_marker("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 4), Some(11));
 }
 
 #[test]
 fn test2() {
-    assert_eq!(find_marker("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14), 19);
-    assert_eq!(find_marker("bvwbjplbgvbhsrlpgdmjqwftvncz", 14), 23);
-    assert_eq!(find_marker("nppdvjthqldpwncqszvftbrmjlhg", 14), 23);
-    assert_eq!(find_marker("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 14), 29);
-    assert_eq!(find_marker("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 14), 26);
+    assert_eq!(find_marker("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14), Some(19));
+    assert_eq!(find_marker("bvwbjplbgvbhsrlpgdmjqwftvncz", 14), Some(23));
+    assert_eq!(find_marker("nppdvjthqldpwncqszvftbrmjlhg", 14), Some(23));
+    assert_eq!(
+        find_marker("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 14),
+        Some(29)
+    );
+    assert_eq!(
+        find_marker("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 14),
+        Some(26)
+    );
+}
+
+#[test]
+fn test_stream() {
+    let stream = "mjqjpqmgbljsphdztnvjfqwrcgsmlb".as_bytes();
+    let markers = MarkerStream::new(stream, &[14, 4])
+        .map(|m| m.unwrap())
+        .collect::<Vec<Marker>>();
+    assert_eq!(
+        markers,
+        vec![
+            Marker {
+                distinct: 4,
+                offset: 7,
+                window: "jpqm".to_string()
+            },
+            Marker {
+                distinct: 14,
+                offset: 19,
+                window: "qmgbljsphdztnv".to_string()
+            }
+        ]
+    );
+
+    // Line terminators count only when more data follows, as in char mode
+    for input in [
+        "mjqjpqmgbljsphdztnvjfqwrcgsmlb\n",
+        "abc\r\nabd\n\n",
+        "ab\ncd\r\n",
+    ] {
+        let streamed = MarkerStream::new(input.as_bytes(), &[3, 4])
+            .every()
+            .map(|m| m.unwrap())
+            .collect::<Vec<Marker>>();
+        let line = input.trim_end_matches(['\r', '\n']);
+        assert_eq!(streamed, find_markers(line, &[3, 4], true, Mode::Char));
+    }
+}
+
+#[test]
+fn test_all_markers() {
+    assert_eq!(find_marker("aaaaaaa", 4), None);
+    assert_eq!(find_marker("abc", 4), None);
+    assert_eq!(
+        markers("mjqjpqmgbl", 4).collect::<Vec<usize>>(),
+        vec![7, 8, 9, 10]
+    );
+    assert_eq!(
+        markers("abcdabcaaa", 4).collect::<Vec<usize>>(),
+        vec![4, 5, 6, 7]
+    );
+}
+
+#[test]
+fn test_decode() {
+    let defs = decoder::parse_definitions("packet=4,message=14").unwrap();
+    let segments = Decoder::new(defs, Mode::Byte).decode("mjqjpqmgbljsphdztnvjfqwrcgsmlb");
+    let summary = segments
+        .iter()
+        .map(|s| (s.kind.as_str(), s.start, s.end, s.marker.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            ("preamble", 0, 7, ""),
+            ("packet", 7, 19, "jpqm"),
+            ("message", 19, 30, "qmgbljsphdztnv")
+        ]
+    );
+    assert_eq!(segments[2].payload, "jfqwrcgsmlb");
+
+    // The packet marker ends halfway through "é"
+    let defs = decoder::parse_definitions("packet=4").unwrap();
+    let segments = Decoder::new(defs, Mode::Byte).decode("aabcé");
+    assert_eq!(segments[1].start, 5);
+    assert_eq!(segments[1].marker, "abc\u{FFFD}");
+    assert_eq!(segments[1].payload, "\u{FFFD}");
+}
+
+#[test]
+fn test_modes() {
+    // "é" is two bytes and "e\u{301}" is two chars forming one grapheme
+    let line = "aébée\u{301}cd";
+    assert_eq!(markers_in(line, 4, Mode::Byte).next(), Some(4));
+    assert_eq!(markers_in(line, 4, Mode::Char).next(), Some(6));
+    assert_eq!(markers_in(line, 4, Mode::Grapheme).next(), Some(6));
+
+    let found = find_markers("ααβγαδ", &[4], false, Mode::Char);
+    assert_eq!(
+        found,
+        vec![Marker {
+            distinct: 4,
+            offset: 6,
+            window: "βγαδ".to_string()
+        }]
+    );
+
+    let found = find_markers("aabcé", &[4], false, Mode::Byte);
+    assert_eq!(found[0].offset, 5);
+    assert_eq!(found[0].window, "abc\u{FFFD}");
 }
@@ -0,0 +1,82 @@
+use crate::Tree;
+use indextree::NodeId;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+// Names come straight from the transcript, don't let them escape the root
+fn check_name(name: &str) -> io::Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("refusing to create entry named {:?}", name),
+        ));
+    }
+    Ok(())
+}
+
+fn write_dir(tree: &Tree, node: NodeId, path: &Path) -> io::Result<()> {
+    fs::create_dir_all(path)?;
+    let dir = tree.arena.get(node).unwrap().get();
+    for f in dir.files.iter() {
+        check_name(&f.name)?;
+        // set_len leaves a hole, so large recorded sizes take no disk space
+        File::create(path.join(&f.name))?.set_len(f.size as u64)?;
+    }
+    for child in node.children(tree.arena) {
+        let name = &tree.arena.get(child).unwrap().get().name;
+        check_name(name)?;
+        write_dir(tree, child, &path.join(name))?;
+    }
+    Ok(())
+}
+
+// Recreates the directories and sparse files of the tree below `root`
+pub fn materialize(tree: &Tree, root: &Path) -> io::Result<()> {
+    write_dir(tree, tree.root.unwrap(), root)
+}
+
+// The transcript splits lines on whitespace, so such names can't be read back
+fn listed_name(entry: &fs::DirEntry) -> io::Result<String> {
+    let name = entry.file_name();
+    match name.to_str() {
+        Some(name) if !name.contains(char::is_whitespace) => Ok(name.to_string()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("cannot list entry named {:?} in a transcript", name),
+        )),
+    }
+}
+
+fn list_dir(path: &Path, out: &mut Vec<String>) -> io::Result<()> {
+    let mut entries = fs::read_dir(path)?.collect::<io::Result<Vec<fs::DirEntry>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    let mut dirs = Vec::new();
+    out.push("$ ls".to_string());
+    for entry in entries {
+        let name = listed_name(&entry)?;
+        // Symlinks are skipped so loops can't make the transcript endless
+        let meta = fs::symlink_metadata(entry.path())?;
+        if meta.is_dir() {
+            out.push(format!("dir {}", name));
+            dirs.push(name);
+        } else if meta.is_file() {
+            out.push(format!("{} {}", meta.len(), name));
+        }
+    }
+    for name in dirs {
+        out.push(format!("$ cd {}", name));
+        list_dir(&path.join(&name), out)?;
+        out.push("$ cd ..".to_string());
+    }
+    Ok(())
+}
+
+// Walks a local directory and writes the terminal session that would
+// have explored it, in the format `parse_transcript` reads
+pub fn transcript(root: &Path) -> io::Result<String> {
+    let mut out = vec!["$ cd /".to_string()];
+    list_dir(root, &mut out)?;
+    Ok(out.join("\n") + "\n")
+}
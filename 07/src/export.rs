@@ -0,0 +1,89 @@
+use crate::Tree;
+use indextree::NodeId;
+use std::fmt::Write;
+
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn write_json(tree: &Tree, node: NodeId, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let dir = tree.arena.get(node).unwrap().get();
+    writeln!(out, "{}{{", indent).unwrap();
+    writeln!(out, "{}  \"name\": \"{}\",", indent, escape(&dir.name)).unwrap();
+    writeln!(out, "{}  \"type\": \"dir\",", indent).unwrap();
+    writeln!(out, "{}  \"size\": {},", indent, tree.total_size(node)).unwrap();
+    write!(out, "{}  \"children\": [", indent).unwrap();
+
+    let mut first = true;
+    for child in node.children(tree.arena) {
+        out.push_str(if first { "\n" } else { ",\n" });
+        first = false;
+        write_json(tree, child, depth + 2, out);
+    }
+    for f in dir.files.iter() {
+        out.push_str(if first { "\n" } else { ",\n" });
+        first = false;
+        write!(
+            out,
+            "{}    {{ \"name\": \"{}\", \"type\": \"file\", \"size\": {} }}",
+            indent,
+            escape(&f.name),
+            f.size
+        )
+        .unwrap();
+    }
+    if !first {
+        write!(out, "\n{}  ", indent).unwrap();
+    }
+    writeln!(out, "]").unwrap();
+    write!(out, "{}}}", indent).unwrap();
+}
+
+// Nested JSON, directories carrying their total size
+pub fn json(tree: &Tree, node: NodeId) -> String {
+    let mut out = String::new();
+    write_json(tree, node, 0, &mut out);
+    out.push('\n');
+    out
+}
+
+fn write_tree(tree: &Tree, node: NodeId, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let dir = tree.arena.get(node).unwrap().get();
+    let size = tree.total_size(node);
+    writeln!(out, "{}- {} (dir, size={})", indent, dir.name, size).unwrap();
+    for child in node.children(tree.arena) {
+        write_tree(tree, child, depth + 1, out);
+    }
+    for f in dir.files.iter() {
+        writeln!(out, "{}  - {} (file, size={})", indent, f.name, f.size).unwrap();
+    }
+}
+
+// Indented view in the format the puzzle uses for its example
+pub fn tree(tree: &Tree, node: NodeId) -> String {
+    let mut out = String::new();
+    write_tree(tree, node, 0, &mut out);
+    out
+}
+
+// One "size<TAB>path" line per directory, deepest first like du(1)
+pub fn du(tree: &Tree, node: NodeId) -> String {
+    let mut out = String::new();
+    for d in node.traverse(tree.arena) {
+        if let indextree::NodeEdge::End(d) = d {
+            writeln!(out, "{}\t{}", tree.total_size(d), tree.path(d)).unwrap();
+        }
+    }
+    out
+}
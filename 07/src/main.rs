@@ -1,7 +1,13 @@
-use indextree::{Arena, Node, NodeId};
+use indextree::{Arena, NodeEdge, NodeId};
 use std::collections::VecDeque;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+mod disk;
+mod export;
+mod planner;
+mod shell;
 
 struct Fil {
     name: String,
@@ -11,12 +17,17 @@ struct Fil {
 struct Dir {
     name: String,
     files: Vec<Fil>,
+    direct_size: usize,
+    total_size: usize,
 }
 
 enum Command {
     Cd(String),
     Ls,
-    Noop,
+    Rm(String),
+    Mkdir(String),
+    Touch(usize, String),
+    Mv(String, String),
 }
 
 impl Fil {
@@ -33,6 +44,8 @@ impl Dir {
         Dir {
             name: name.to_string(),
             files: Vec::new(),
+            direct_size: 0,
+            total_size: 0,
         }
     }
 
@@ -41,121 +54,687 @@ impl Dir {
     }
 }
 
-fn parse_command(tokens: &mut VecDeque<&str>) -> Command {
-    let command = tokens.pop_front().unwrap();
-    if command == "cd" {
-        let dir_name = tokens.pop_front().unwrap();
-        return Command::Cd(dir_name.to_string());
-    } else if command == "ls" {
-        return Command::Ls;
+fn parse_command(tokens: &mut VecDeque<&str>) -> Result<Command, String> {
+    let command = tokens.pop_front().ok_or("missing command")?;
+    let mut arg = || {
+        tokens
+            .pop_front()
+            .map(|a| a.to_string())
+            .ok_or(format!("{}: missing operand", command))
+    };
+    match command {
+        "cd" => Ok(Command::Cd(arg()?)),
+        "ls" => Ok(Command::Ls),
+        "rm" => Ok(Command::Rm(arg()?)),
+        "mkdir" => Ok(Command::Mkdir(arg()?)),
+        "touch" => {
+            let size = arg()?;
+            let size = size
+                .parse::<usize>()
+                .map_err(|_| format!("touch: invalid size: {}", size))?;
+            Ok(Command::Touch(size, arg()?))
+        }
+        "mv" => Ok(Command::Mv(arg()?, arg()?)),
+        _ => Err(format!("{}: unknown command", command)),
     }
-    Command::Noop
+}
+
+enum Entry {
+    Dir(NodeId),
+    File(usize),
 }
 
 struct Tree<'a> {
     arena: &'a mut Arena<Dir>,
     root: Option<NodeId>,
     current_dir: Option<NodeId>,
+    sizes_dirty: bool,
 }
 
-fn perform_command(cmd: Command, tree: &mut Tree) {
-    match cmd {
-        Command::Ls => (),
-        Command::Noop => (),
-        Command::Cd(dir_name) => {
-            if dir_name == ".." {
-                if let Some(cur) = tree.current_dir {
-                    let node = tree.arena.get(cur).unwrap();
-                    let parent = node.parent().unwrap();
-                    tree.current_dir = Some(parent);
-                }
+impl<'a> Tree<'a> {
+    fn new(arena: &'a mut Arena<Dir>) -> Tree<'a> {
+        Tree {
+            arena,
+            root: None,
+            current_dir: None,
+            sizes_dirty: false,
+        }
+    }
+
+    fn root(&mut self) -> NodeId {
+        match self.root {
+            Some(root) => root,
+            None => {
+                let root = self.arena.new_node(Dir::new("/"));
+                self.root = Some(root);
+                root
+            }
+        }
+    }
+
+    fn child(&self, parent: NodeId, name: &str) -> Option<NodeId> {
+        parent
+            .children(self.arena)
+            .find(|c| self.arena.get(*c).unwrap().get().name == name)
+    }
+
+    fn path(&self, node: NodeId) -> String {
+        let mut names: Vec<&str> = node
+            .ancestors(self.arena)
+            .map(|a| self.arena.get(a).unwrap().get().name.as_str())
+            .filter(|name| *name != "/")
+            .collect();
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    fn resolve(&self, from: NodeId, path: &str) -> Option<NodeId> {
+        let mut node = if path.starts_with('/') {
+            self.root?
+        } else {
+            from
+        };
+        for part in path.split('/').filter(|p| !p.is_empty() && *p != ".") {
+            node = if part == ".." {
+                self.arena.get(node)?.parent().unwrap_or(node)
             } else {
-                let dir = Dir::new(&dir_name);
-                let new_dir = tree.arena.new_node(dir);
-                if let Some(cur) = tree.current_dir {
-                    cur.append(new_dir, tree.arena);
-                } else {
-                    tree.root = Some(new_dir);
+                self.child(node, part)?
+            };
+        }
+        Some(node)
+    }
+
+    // Splits `path` into the directory it lives in and its last component
+    fn locate<'p>(&mut self, path: &'p str) -> Result<(NodeId, &'p str), String> {
+        let cur = self.current_dir.unwrap_or_else(|| self.root());
+        let (dir_path, name) = match path.rsplit_once('/') {
+            Some(("", name)) => ("/", name),
+            Some((dir_path, name)) => (dir_path, name),
+            None => (".", path),
+        };
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(format!("{}: invalid name", path));
+        }
+        let parent = self
+            .resolve(cur, dir_path)
+            .ok_or(format!("{}: No such directory", dir_path))?;
+        Ok((parent, name))
+    }
+
+    fn entry(&self, parent: NodeId, name: &str) -> Option<Entry> {
+        if let Some(dir) = self.child(parent, name) {
+            return Some(Entry::Dir(dir));
+        }
+        let dir = self.arena.get(parent).unwrap().get();
+        dir.files
+            .iter()
+            .position(|f| f.name == name)
+            .map(Entry::File)
+    }
+
+    fn mkdir(&mut self, path: &str) -> Result<(), String> {
+        let (parent, name) = self.locate(path)?;
+        if self.entry(parent, name).is_some() {
+            return Err(format!("mkdir: {}: File exists", path));
+        }
+        let new_dir = self.arena.new_node(Dir::new(name));
+        parent.append(new_dir, self.arena);
+        self.sizes_dirty = true;
+        Ok(())
+    }
+
+    // Creates the file, or sets the size of an existing one
+    fn touch(&mut self, path: &str, size: usize) -> Result<(), String> {
+        let (parent, name) = self.locate(path)?;
+        let entry = self.entry(parent, name);
+        let dir = self.arena.get_mut(parent).unwrap().get_mut();
+        match entry {
+            Some(Entry::Dir(_)) => return Err(format!("touch: {}: Is a directory", path)),
+            Some(Entry::File(index)) => dir.files[index].size = size,
+            None => dir.files.push(Fil::new(name, size)),
+        }
+        self.sizes_dirty = true;
+        Ok(())
+    }
+
+    // Removes files and whole directories, like rm -r
+    fn rm(&mut self, path: &str) -> Result<(), String> {
+        let (parent, name) = self.locate(path)?;
+        match self.entry(parent, name) {
+            Some(Entry::Dir(dir)) => {
+                let inside = self
+                    .current_dir
+                    .is_some_and(|cur| cur.ancestors(self.arena).any(|a| a == dir));
+                if inside {
+                    self.current_dir = Some(parent);
+                }
+                dir.remove_subtree(self.arena);
+            }
+            Some(Entry::File(index)) => {
+                self.arena
+                    .get_mut(parent)
+                    .unwrap()
+                    .get_mut()
+                    .files
+                    .remove(index);
+            }
+            None => return Err(format!("rm: {}: No such file or directory", path)),
+        }
+        self.sizes_dirty = true;
+        Ok(())
+    }
+
+    // Moves into `dst` when it is an existing directory, otherwise renames
+    fn mv(&mut self, src: &str, dst: &str) -> Result<(), String> {
+        let (src_parent, src_name) = self.locate(src)?;
+        let entry = self
+            .entry(src_parent, src_name)
+            .ok_or(format!("mv: {}: No such file or directory", src))?;
+        let cur = self.current_dir.unwrap_or_else(|| self.root());
+        let (target, name) = match self.resolve(cur, dst) {
+            Some(dir) => (dir, src_name.to_string()),
+            None => {
+                let (parent, name) = self.locate(dst)?;
+                (parent, name.to_string())
+            }
+        };
+        if self.entry(target, &name).is_some() {
+            return Err(format!("mv: {}: File exists", dst));
+        }
+
+        match entry {
+            Entry::Dir(dir) => {
+                if target.ancestors(self.arena).any(|a| a == dir) {
+                    return Err(format!("mv: cannot move {} into itself", src));
                 }
-                tree.current_dir = Some(new_dir);
+                dir.detach(self.arena);
+                target.append(dir, self.arena);
+                self.arena.get_mut(dir).unwrap().get_mut().name = name;
+            }
+            Entry::File(index) => {
+                let mut file = self
+                    .arena
+                    .get_mut(src_parent)
+                    .unwrap()
+                    .get_mut()
+                    .files
+                    .remove(index);
+                file.name = name;
+                self.arena
+                    .get_mut(target)
+                    .unwrap()
+                    .get_mut()
+                    .files
+                    .push(file);
+            }
+        }
+        self.sizes_dirty = true;
+        Ok(())
+    }
+
+    // Returns the existing child directory so revisits don't duplicate it
+    fn add_dir(&mut self, name: &str) -> NodeId {
+        let cur = self.current_dir.unwrap_or_else(|| self.root());
+        self.add_dir_in(cur, name)
+    }
+
+    fn add_dir_in(&mut self, parent: NodeId, name: &str) -> NodeId {
+        if let Some(existing) = self.child(parent, name) {
+            return existing;
+        }
+        let new_dir = self.arena.new_node(Dir::new(name));
+        parent.append(new_dir, self.arena);
+        self.sizes_dirty = true;
+        new_dir
+    }
+
+    // Follows `path` like the other commands; a missing last component is
+    // created, as the transcript may cd into a directory before listing it
+    fn cd(&mut self, path: &str) -> Result<(), String> {
+        let cur = self.current_dir.unwrap_or_else(|| self.root());
+        let dir = match self.resolve(cur, path) {
+            Some(dir) => dir,
+            None => {
+                let (parent, name) = self.locate(path).map_err(|e| format!("cd: {}", e))?;
+                self.add_dir_in(parent, name)
             }
+        };
+        self.current_dir = Some(dir);
+        Ok(())
+    }
+
+    // Repeated `ls` output lists the same files again, only keep the first
+    fn add_file(&mut self, name: &str, size: usize) {
+        let cur = self.current_dir.unwrap_or_else(|| self.root());
+        let dir = self.arena.get_mut(cur).unwrap().get_mut();
+        if !dir.files.iter().any(|f| f.name == name) {
+            dir.files.push(Fil::new(name, size));
+            self.sizes_dirty = true;
         }
     }
+
+    // Caches direct and total sizes on every directory in a single
+    // post-order pass, so children are always summed before their parent
+    fn compute_sizes(&mut self) {
+        let root = match self.root {
+            Some(root) => root,
+            None => return,
+        };
+        let post_order: Vec<NodeId> = root
+            .traverse(self.arena)
+            .filter_map(|edge| match edge {
+                NodeEdge::End(id) => Some(id),
+                NodeEdge::Start(_) => None,
+            })
+            .collect();
+        for id in post_order {
+            let direct = self.arena.get(id).unwrap().get().size();
+            let nested: usize = id
+                .children(self.arena)
+                .map(|c| self.arena.get(c).unwrap().get().total_size)
+                .sum();
+            let dir = self.arena.get_mut(id).unwrap().get_mut();
+            dir.direct_size = direct;
+            dir.total_size = direct + nested;
+        }
+        self.sizes_dirty = false;
+    }
+
+    fn total_size(&self, node: NodeId) -> usize {
+        debug_assert!(!self.sizes_dirty, "compute_sizes must run after changes");
+        self.arena.get(node).unwrap().get().total_size
+    }
+
+    fn direct_size(&self, node: NodeId) -> usize {
+        debug_assert!(!self.sizes_dirty, "compute_sizes must run after changes");
+        self.arena.get(node).unwrap().get().direct_size
+    }
+}
+
+fn perform_command(cmd: Command, tree: &mut Tree) -> Result<(), String> {
+    match cmd {
+        Command::Ls => (),
+        Command::Rm(path) => tree.rm(&path)?,
+        Command::Mkdir(path) => tree.mkdir(&path)?,
+        Command::Touch(size, path) => tree.touch(&path, size)?,
+        Command::Mv(src, dst) => tree.mv(&src, &dst)?,
+        Command::Cd(path) => tree.cd(&path)?,
+    }
+    Ok(())
 }
 
-fn dir_size(tree: &Tree, node_id: NodeId) -> usize {
-    if let Some(_node) = tree.arena.get(node_id) {
-        let sum = node_id.descendants(tree.arena).fold(0, |sum, d| {
-            let nd = tree.arena.get(d).unwrap().get();
-            sum + nd.size()
+fn parse_line(line: &str, tree: &mut Tree) -> Result<(), String> {
+    let mut tokens = line.split_ascii_whitespace().collect::<VecDeque<&str>>();
+    let first = match tokens.pop_front() {
+        Some(first) => first,
+        None => return Ok(()),
+    };
+    if first == "$" {
+        let cmd = parse_command(&mut tokens)?;
+        perform_command(cmd, tree)?;
+    } else if first == "dir" {
+        let dir_name = tokens.pop_front().ok_or("dir: missing name")?;
+        tree.add_dir(dir_name);
+    } else if let Ok(size) = first.parse::<usize>() {
+        let file_name = tokens.pop_front().ok_or("file: missing name")?;
+        tree.add_file(file_name, size);
+    } else {
+        return Err(format!("unrecognised output: {}", line));
+    }
+    Ok(())
+}
+
+// Replays the whole transcript, returning the lines that could not be applied
+fn parse_transcript(reader: impl BufRead, tree: &mut Tree) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (index, line) in reader.lines().map_while(Result::ok).enumerate() {
+        if let Err(e) = parse_line(&line, tree) {
+            errors.push(format!("line {}: {}", index + 1, e));
+        }
+    }
+    tree.compute_sizes();
+    errors
+}
+
+fn print_hierarchy(tree: &Tree) {
+    println!("Hierachy:");
+    tree.root.unwrap().descendants(tree.arena).for_each(|d| {
+        let dir = tree.arena.get(d).unwrap().get();
+        println!("{} - {}", dir.name, tree.total_size(d));
+        dir.files.iter().for_each(|f| {
+            println!("\t{} - {}", f.name, f.size);
         });
-        return sum;
+    });
+}
+
+// Total of all directories of at most `limit` in size, counting nested ones again
+fn small_dirs_size(tree: &Tree, limit: usize) -> usize {
+    tree.root
+        .unwrap()
+        .descendants(tree.arena)
+        .map(|d| tree.total_size(d))
+        .filter(|size| *size <= limit)
+        .sum()
+}
+
+fn size_arg(flag: &str, value: Option<String>) -> usize {
+    let value = value.unwrap_or_default();
+    match value.parse() {
+        Ok(size) => size,
+        Err(_) => {
+            eprintln!("{}: invalid size: {:?}", flag, value);
+            std::process::exit(1);
+        }
     }
-    0
 }
 
+// Usage: aoc-07 [--shell] [--export json|tree|du] [--materialize <dir>] [--from-dir <dir>]
+//               [--disk <size>] [--free <size>] [--small <size>] [--set] [filename]
 fn main() {
-    let filename = "input";
-    let file = File::open(filename).unwrap();
+    let mut filename = "input".to_string();
+    let mut interactive = false;
+    let mut disk_size = 70000000;
+    let mut required_free = 30000000;
+    let mut small_limit = 100000;
+    let mut plan_set = false;
+    let mut export_format = None;
+    let mut materialize_to = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--shell" => interactive = true,
+            "--disk" => disk_size = size_arg(&arg, args.next()),
+            "--free" => required_free = size_arg(&arg, args.next()),
+            "--small" => small_limit = size_arg(&arg, args.next()),
+            "--set" => plan_set = true,
+            "--export" => export_format = args.next(),
+            "--materialize" => materialize_to = args.next(),
+            "--from-dir" => {
+                let dir = args.next().unwrap_or(".".to_string());
+                match disk::transcript(Path::new(&dir)) {
+                    Ok(transcript) => print!("{}", transcript),
+                    Err(e) => {
+                        eprintln!("{}: {}", dir, e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+            _ => filename = arg,
+        }
+    }
+
+    let file = File::open(&filename).unwrap();
     let reader = BufReader::new(file);
 
     let arena: &mut Arena<Dir> = &mut Arena::new();
 
-    let mut tree = Tree {
-        arena,
-        root: None,
-        current_dir: None,
-    };
+    let mut tree = Tree::new(arena);
 
-    for line in reader.lines().flatten() {
-        let mut tokens = line.split_ascii_whitespace().collect::<VecDeque<&str>>();
-        let first = tokens.pop_front().unwrap().clone();
-        if first == "$" {
-            let cmd = parse_command(&mut tokens);
-            perform_command(cmd, &mut tree);
-        } else if first == "dir" {
-            // let dir_name = tokens.pop_front().unwrap();
-            // let dir = Dir::new(dir_name);
-        } else if first.chars().next().unwrap().is_numeric() {
-            let file_name = tokens.pop_front().unwrap();
-            let size = first.parse::<usize>().unwrap();
-            let file = Fil::new(file_name, size);
-            if let Some(node) = tree.arena.get_mut(tree.current_dir.unwrap()) {
-                let d = node.get_mut();
-                d.files.push(file);
-            }
-        }
+    for error in parse_transcript(reader, &mut tree) {
+        eprintln!("{}: {}", filename, error);
     }
 
-    let total_size = 70000000;
-    let required_size = 40000000;
-    let used_size = dir_size(&tree, tree.root.unwrap());
-    let missing = used_size - required_size;
+    if interactive {
+        shell::Shell::new(&tree).run();
+        return;
+    }
 
-    println!("Hierachy:");
-    let mut filtered_size = 0;
-    let mut prospects = Vec::new();
-    tree.root.unwrap().descendants(tree.arena).for_each(|d| {
-        let node = tree.arena.get(d).unwrap();
-        let dir = node.get();
-        let dir_size = dir_size(&tree, d);
-        if dir_size <= 100000 {
-            filtered_size += dir_size;
+    if let Some(dir) = materialize_to {
+        if let Err(e) = disk::materialize(&tree, Path::new(&dir)) {
+            eprintln!("{}: {}", dir, e);
+            std::process::exit(1);
         }
-        if dir_size > missing {
-            prospects.push(d);
+        return;
+    }
+
+    if let Some(format) = export_format {
+        let root = tree.root.unwrap();
+        match format.as_str() {
+            "json" => print!("{}", export::json(&tree, root)),
+            "tree" => print!("{}", export::tree(&tree, root)),
+            "du" => print!("{}", export::du(&tree, root)),
+            _ => {
+                eprintln!("Unknown export format: {}", format);
+                std::process::exit(1);
+            }
         }
-        println!("{} - {}", dir.name, dir_size);
-        dir.files.iter().for_each(|f| {
-            println!("\t{} - {}", f.name, f.size);
-        });
-    });
-    println!("Filtered size: {}", filtered_size);
+        return;
+    }
+
+    print_hierarchy(&tree);
+    println!("Filtered size: {}", small_dirs_size(&tree, small_limit));
 
-    println!("Used size {}", used_size);
+    let planner = planner::Planner::new(disk_size, required_free);
+    let missing = planner.missing(&tree);
+    println!("Used size {}", tree.total_size(tree.root.unwrap()));
     println!("Missing: {}", missing);
 
-    let min_dir = prospects.iter().min_by_key(|d| dir_size(&tree, **d));
-    let min_dir_size = dir_size(&tree, *min_dir.unwrap());
-    println!("Min dir to delete: {}", min_dir_size);
+    if missing == 0 {
+        println!("Enough free space, nothing to delete");
+        return;
+    }
+
+    match planner.smallest_dir(&tree) {
+        Some(d) => println!("Min dir to delete: {}", tree.total_size(d)),
+        None => println!("No single directory frees enough space"),
+    }
+
+    if plan_set {
+        match planner.minimal_set(&tree) {
+            Some(dirs) => {
+                let total: usize = dirs.iter().map(|d| tree.total_size(*d)).sum();
+                println!("Min set to delete: {}", total);
+                for d in dirs {
+                    println!("\t{} - {}", tree.path(d), tree.total_size(d));
+                }
+            }
+            None => println!("No set of directories frees enough space"),
+        }
+    }
+}
+
+#[cfg(test)]
+fn read_tree<'a>(arena: &'a mut Arena<Dir>, filename: &str) -> Tree<'a> {
+    let mut tree = Tree::new(arena);
+    parse_transcript(BufReader::new(File::open(filename).unwrap()), &mut tree);
+    tree
+}
+
+#[test]
+fn test_revisits() {
+    let arena = &mut Arena::new();
+    let mut tree = Tree::new(arena);
+    let transcript = [
+        "$ cd /",
+        "$ ls",
+        "dir a",
+        "14848514 b.txt",
+        "$ cd a",
+        "$ ls",
+        "29116 f",
+        "$ cd /",
+        "$ ls",
+        "dir a",
+        "14848514 b.txt",
+        "$ cd a",
+        "$ ls",
+        "29116 f",
+        "$ cd ..",
+        "$ cd a",
+    ];
+    transcript
+        .iter()
+        .for_each(|l| parse_line(l, &mut tree).unwrap());
+    tree.compute_sizes();
+
+    let root = tree.root.unwrap();
+    assert_eq!(root.descendants(tree.arena).count(), 2);
+    assert_eq!(tree.total_size(root), 14848514 + 29116);
+    assert_eq!(tree.direct_size(root), 14848514);
+    assert_eq!(tree.current_dir, tree.child(root, "a"));
+}
+
+#[test]
+fn test_shell() {
+    let arena = &mut Arena::new();
+    let tree = read_tree(arena, "input-example");
+
+    let mut shell = shell::Shell::new(&tree);
+    assert_eq!(shell.execute("cd a/e"), Ok(String::new()));
+    assert_eq!(shell.execute("pwd"), Ok("/a/e\n".to_string()));
+    assert_eq!(shell.execute("ls"), Ok("584 i\n".to_string()));
+    assert_eq!(shell.execute("du /a"), Ok("94853\t/a\n".to_string()));
+    assert_eq!(shell.execute("du -S /a"), Ok("94269\t/a\n".to_string()));
+    assert_eq!(
+        shell.execute("find / -size -100000"),
+        Ok(
+            "94853\t/a\n29116\t/a/f\n2557\t/a/g\n62596\t/a/h.lst\n584\t/a/e\n584\t/a/e/i\n"
+                .to_string()
+        )
+    );
+    assert!(shell.execute("cd x").is_err());
+}
+
+#[test]
+fn test_planner() {
+    let arena = &mut Arena::new();
+    let tree = read_tree(arena, "input-example");
+
+    assert_eq!(small_dirs_size(&tree, 100000), 95437);
+
+    let planner = planner::Planner::new(70000000, 30000000);
+    assert_eq!(planner.missing(&tree), 8381165);
+    let smallest = planner.smallest_dir(&tree).unwrap();
+    assert_eq!(tree.total_size(smallest), 24933642);
+
+    let set = planner.minimal_set(&tree).unwrap();
+    assert_eq!(set, vec![smallest]);
+
+    // a (94853) plus d frees just enough where d alone is too small
+    let planner = planner::Planner::new(48381165, 25028495);
+    assert_eq!(planner.missing(&tree), 25028495);
+    assert_eq!(planner.smallest_dir(&tree), tree.root);
+    let set = planner.minimal_set(&tree).unwrap();
+    let total: usize = set.iter().map(|d| tree.total_size(*d)).sum();
+    assert_eq!(total, 94853 + 24933642);
+
+    let planner = planner::Planner::new(70000000, 1000);
+    assert_eq!(planner.missing(&tree), 0);
+    assert_eq!(planner.smallest_dir(&tree), None);
+
+    let planner = planner::Planner::new(1000, 100000000);
+    assert_eq!(planner.smallest_dir(&tree), None);
+    assert_eq!(planner.minimal_set(&tree), None);
+}
+
+#[test]
+fn test_export() {
+    let arena = &mut Arena::new();
+    let tree = read_tree(arena, "input-example");
+    let root = tree.root.unwrap();
+
+    assert_eq!(
+        export::du(&tree, root),
+        "584\t/a/e\n94853\t/a\n24933642\t/d\n48381165\t/\n"
+    );
+    let a = tree.child(root, "a").unwrap();
+    assert_eq!(
+        export::tree(&tree, a),
+        "- a (dir, size=94853)\n  - e (dir, size=584)\n    - i (file, size=584)\n  \
+         - f (file, size=29116)\n  - g (file, size=2557)\n  - h.lst (file, size=62596)\n"
+    );
+    let e = tree.child(a, "e").unwrap();
+    assert_eq!(
+        export::json(&tree, e),
+        "{\n  \"name\": \"e\",\n  \"type\": \"dir\",\n  \"size\": 584,\n  \"children\": [\n    \
+         { \"name\": \"i\", \"type\": \"file\", \"size\": 584 }\n  ]\n}\n"
+    );
+}
+
+#[test]
+fn test_disk_roundtrip() {
+    let arena = &mut Arena::new();
+    let tree = read_tree(arena, "input-example");
+
+    let dir = std::env::temp_dir().join(format!("aoc-07-{}", std::process::id()));
+    disk::materialize(&tree, &dir).unwrap();
+    assert_eq!(std::fs::metadata(dir.join("d/k")).unwrap().len(), 7214296);
+
+    let transcript = disk::transcript(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let arena = &mut Arena::new();
+    let mut copy = Tree::new(arena);
+    parse_transcript(transcript.as_bytes(), &mut copy);
+    let root = copy.root.unwrap();
+    assert_eq!(
+        export::du(&copy, root),
+        "584\t/a/e\n94853\t/a\n24933642\t/d\n48381165\t/\n"
+    );
+
+    // A name the transcript would split is refused rather than read back wrong
+    let dir = std::env::temp_dir().join(format!("aoc-07-spaces-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    File::create(dir.join("sub/my file.txt")).unwrap();
+    let result = disk::transcript(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let error = result.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("\"my file.txt\""));
+}
+
+#[test]
+fn test_mutations() {
+    let arena = &mut Arena::new();
+    let mut tree = read_tree(arena, "input-example");
+
+    let transcript = "$ cd /
+$ mkdir x
+$ touch 100 x/y
+$ mv d x
+$ mv a/e/i a/moved
+$ rm a/e
+$ touch 1000 b.txt
+$ rm c.dat
+$ frobnicate
+$ rm nothing
+$ mv x x/d
+";
+    let errors = parse_transcript(transcript.as_bytes(), &mut tree);
+    assert_eq!(
+        errors,
+        vec![
+            "line 9: frobnicate: unknown command",
+            "line 10: rm: nothing: No such file or directory",
+            "line 11: mv: cannot move x into itself",
+        ]
+    );
+    let root = tree.root.unwrap();
+    assert_eq!(
+        export::du(&tree, root),
+        "94853\t/a\n24933642\t/x/d\n24933742\t/x\n25029595\t/\n"
+    );
+
+    // cd follows paths into directories made by mkdir instead of
+    // creating one literally named "a/b"
+    let arena = &mut Arena::new();
+    let mut tree = Tree::new(arena);
+    let transcript = "$ cd /
+$ mkdir a
+$ mkdir a/b
+$ cd a/b
+$ touch 5 f
+$ cd ../../a/./b/c
+$ touch 7 g
+$ cd /a/missing/c
+";
+    let errors = parse_transcript(transcript.as_bytes(), &mut tree);
+    assert_eq!(errors, vec!["line 8: cd: /a/missing: No such directory"]);
+    let root = tree.root.unwrap();
+    assert_eq!(
+        export::du(&tree, root),
+        "7\t/a/b/c\n12\t/a/b\n12\t/a\n12\t/\n"
+    );
 }
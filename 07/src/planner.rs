@@ -0,0 +1,113 @@
+use crate::Tree;
+use indextree::NodeId;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+// Directories picked for deletion, shared between the options built on top
+// of them so combining two choices doesn't copy either
+enum Choice {
+    Nothing,
+    Dir(NodeId),
+    Both(Rc<Choice>, Rc<Choice>),
+}
+
+impl Choice {
+    fn collect(&self, dirs: &mut Vec<NodeId>) {
+        match self {
+            Choice::Nothing => (),
+            Choice::Dir(d) => dirs.push(*d),
+            Choice::Both(a, b) => {
+                a.collect(dirs);
+                b.collect(dirs);
+            }
+        }
+    }
+}
+
+// Directory choices for a subtree, keyed by the total they free
+type Options = BTreeMap<usize, Rc<Choice>>;
+
+pub struct Planner {
+    disk_size: usize,
+    required_free: usize,
+}
+
+impl Planner {
+    pub fn new(disk_size: usize, required_free: usize) -> Planner {
+        Planner {
+            disk_size,
+            required_free,
+        }
+    }
+
+    // How much has to be deleted, zero when there already is enough space
+    pub fn missing(&self, tree: &Tree) -> usize {
+        let used = tree.total_size(tree.root.unwrap());
+        let free = self.disk_size.saturating_sub(used);
+        self.required_free.saturating_sub(free)
+    }
+
+    // The smallest directory that frees enough space on its own
+    pub fn smallest_dir(&self, tree: &Tree) -> Option<NodeId> {
+        let missing = self.missing(tree);
+        if missing == 0 {
+            return None;
+        }
+        tree.root
+            .unwrap()
+            .descendants(tree.arena)
+            .filter(|d| tree.total_size(*d) >= missing)
+            .min_by_key(|d| tree.total_size(*d))
+    }
+
+    // The set of directories freeing enough space while deleting the least
+    // in total. Only disjoint directories are combined, since deleting a
+    // directory also deletes everything below it.
+    pub fn minimal_set(&self, tree: &Tree) -> Option<Vec<NodeId>> {
+        let missing = self.missing(tree);
+        let root = tree.root.unwrap();
+        // Even deleting everything wouldn't do, don't enumerate every option
+        if missing == 0 || tree.total_size(root) < missing {
+            return None;
+        }
+        let options = self.options(tree, root, missing);
+        options.range(missing..).next().map(|(_total, choice)| {
+            let mut dirs = Vec::new();
+            choice.collect(&mut dirs);
+            dirs
+        })
+    }
+
+    fn options(&self, tree: &Tree, node: NodeId, missing: usize) -> Options {
+        let mut combined = Options::new();
+        combined.insert(0, Rc::new(Choice::Nothing));
+        for child in node.children(tree.arena) {
+            let child_options = self.options(tree, child, missing);
+            let mut next = Options::new();
+            for (total, choice) in combined.iter() {
+                for (child_total, child_choice) in child_options.iter() {
+                    let both = Choice::Both(choice.clone(), child_choice.clone());
+                    insert(&mut next, total + child_total, Rc::new(both), missing);
+                }
+            }
+            combined = next;
+        }
+        let dir = Rc::new(Choice::Dir(node));
+        insert(&mut combined, tree.total_size(node), dir, missing);
+        combined
+    }
+}
+
+// Totals below `missing` are all kept as they may still be topped up;
+// of the totals reaching it only the smallest can ever be the answer
+fn insert(options: &mut Options, total: usize, choice: Rc<Choice>, missing: usize) {
+    if total >= missing {
+        if let Some((&best, _dirs)) = options.range(missing..).next() {
+            if best <= total {
+                return;
+            }
+            options.remove(&best);
+        }
+    }
+    options.entry(total).or_insert(choice);
+}
@@ -0,0 +1,125 @@
+use crate::{export, Tree};
+use indextree::NodeId;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+
+// Read-only shell over a reconstructed tree, with its own working directory
+pub struct Shell<'t, 'a> {
+    tree: &'t Tree<'a>,
+    cwd: NodeId,
+}
+
+impl<'t, 'a> Shell<'t, 'a> {
+    pub fn new(tree: &'t Tree<'a>) -> Shell<'t, 'a> {
+        Shell {
+            tree,
+            cwd: tree.root.unwrap(),
+        }
+    }
+
+    fn resolve_arg(&self, arg: Option<&str>) -> Result<NodeId, String> {
+        let path = arg.unwrap_or(".");
+        self.tree
+            .resolve(self.cwd, path)
+            .ok_or(format!("{}: No such directory", path))
+    }
+
+    fn ls(&self, node: NodeId) -> String {
+        let mut out = String::new();
+        for child in node.children(self.tree.arena) {
+            let dir = self.tree.arena.get(child).unwrap().get();
+            writeln!(out, "dir {}", dir.name).unwrap();
+        }
+        for f in self.tree.arena.get(node).unwrap().get().files.iter() {
+            writeln!(out, "{} {}", f.size, f.name).unwrap();
+        }
+        out
+    }
+
+    // Matches find(1): `+N` is larger than N, `-N` smaller, `N` exactly N
+    fn find(&self, node: NodeId, size: &str) -> Result<String, String> {
+        let (cmp, num): (fn(usize, usize) -> bool, &str) = match size.chars().next() {
+            Some('+') => (|a, b| a > b, &size[1..]),
+            Some('-') => (|a, b| a < b, &size[1..]),
+            _ => (|a, b| a == b, size),
+        };
+        let limit = num
+            .parse::<usize>()
+            .map_err(|_| format!("find: invalid size: {}", size))?;
+
+        let mut out = String::new();
+        for d in node.descendants(self.tree.arena) {
+            let path = self.tree.path(d);
+            let total = self.tree.total_size(d);
+            if cmp(total, limit) {
+                writeln!(out, "{}\t{}", total, path).unwrap();
+            }
+            for f in self.tree.arena.get(d).unwrap().get().files.iter() {
+                if cmp(f.size, limit) {
+                    let sep = if path.ends_with('/') { "" } else { "/" };
+                    writeln!(out, "{}\t{}{}{}", f.size, path, sep, f.name).unwrap();
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn execute(&mut self, line: &str) -> Result<String, String> {
+        let mut tokens = line.split_ascii_whitespace();
+        let command = match tokens.next() {
+            Some(command) => command,
+            None => return Ok(String::new()),
+        };
+        match command {
+            "cd" => {
+                self.cwd = self.resolve_arg(Some(tokens.next().unwrap_or("/")))?;
+                Ok(String::new())
+            }
+            "ls" => Ok(self.ls(self.resolve_arg(tokens.next())?)),
+            "pwd" => Ok(format!("{}\n", self.tree.path(self.cwd))),
+            "du" => {
+                // Like du(1), -S leaves out the size of subdirectories
+                let mut tokens = tokens.peekable();
+                let separate = tokens.next_if_eq(&"-S").is_some();
+                let node = self.resolve_arg(tokens.next())?;
+                let size = if separate {
+                    self.tree.direct_size(node)
+                } else {
+                    self.tree.total_size(node)
+                };
+                Ok(format!("{}\t{}\n", size, self.tree.path(node)))
+            }
+            "find" => {
+                let args: Vec<&str> = tokens.collect();
+                match args.as_slice() {
+                    ["-size", size] => self.find(self.cwd, size),
+                    [path, "-size", size] => self.find(self.resolve_arg(Some(path))?, size),
+                    _ => Err("usage: find [path] -size [+|-]N".to_string()),
+                }
+            }
+            "tree" => Ok(export::tree(self.tree, self.resolve_arg(tokens.next())?)),
+            _ => Err(format!("{}: command not found", command)),
+        }
+    }
+
+    pub fn run(&mut self) {
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            print!("{} $ ", self.tree.path(self.cwd));
+            io::stdout().flush().unwrap();
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                _ => break,
+            };
+            if line.trim() == "exit" {
+                break;
+            }
+            match self.execute(&line) {
+                Ok(out) => print!("{}", out),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        println!();
+    }
+}
@@ -0,0 +1,56 @@
+use crate::grid::Grid;
+use crate::{parallel, scenic_scores, visibility};
+use std::time::{Duration, Instant};
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+}
+
+// A square forest of random heights
+pub fn generate(size: usize) -> Grid<u32> {
+    let mut rng = XorShift(0x2022_0008);
+    let mut trees = Grid::new(size, size, 0);
+    for y in 0..size {
+        for x in 0..size {
+            trees[(x, y)] = rng.next(10) as u32;
+        }
+    }
+    trees
+}
+
+fn time<T, F: FnOnce() -> T>(f: F) -> (Duration, T) {
+    let start = Instant::now();
+    let result = f();
+    (start.elapsed(), result)
+}
+
+pub fn run(size: usize) {
+    let trees = generate(size);
+    println!(
+        "Benchmark: {}x{} forest, {} threads",
+        size,
+        size,
+        rayon::current_num_threads()
+    );
+
+    let (sequential, visible) = time(|| visibility(&trees));
+    let (parallel, par_visible) = time(|| parallel::visibility(&trees));
+    assert_eq!(visible, par_visible);
+    println!("Visibility:");
+    println!("\tsequential: {:?}", sequential);
+    println!("\tparallel:   {:?}", parallel);
+
+    let (sequential, scores) = time(|| scenic_scores(&trees));
+    let (parallel, par_scores) = time(|| parallel::scenic_scores(&trees));
+    assert_eq!(scores, par_scores);
+    println!("Scenic scores:");
+    println!("\tsequential: {:?}", sequential);
+    println!("\tparallel:   {:?}", parallel);
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+// A rectangular grid stored row by row, indexed as grid[(x, y)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Grid<T> {
+        Grid {
+            width,
+            height,
+            cells: vec![value; width * height],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn from_fn<F: FnMut(usize, usize) -> T>(width: usize, height: usize, mut f: F) -> Grid<T> {
+        Grid {
+            width,
+            height,
+            cells: (0..width * height)
+                .map(|i| f(i % width, i / width))
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        if x < self.width && y < self.height {
+            self.cells.get(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    // The cell one step of (dx, dy) away, if it is still on the grid
+    pub fn neighbour(&self, x: usize, y: usize, dx: isize, dy: isize) -> Option<(usize, usize)> {
+        let nx = x.checked_add_signed(dx)?;
+        let ny = y.checked_add_signed(dy)?;
+        self.get(nx, ny).map(|_| (nx, ny))
+    }
+
+    // Coordinates from `start` stepping by (dx, dy) until the edge
+    pub fn walk(
+        &self,
+        start: (usize, usize),
+        (dx, dy): (isize, isize),
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let first = self.get(start.0, start.1).map(|_| start);
+        std::iter::successors(first, move |(x, y)| self.neighbour(*x, *y, dx, dy))
+    }
+
+    pub fn row(&self, y: usize) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        assert!(y < self.height, "row {} is off the grid", y);
+        self.cells[y * self.width..(y + 1) * self.width].iter()
+    }
+
+    pub fn column(&self, x: usize) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        assert!(x < self.width, "column {} is off the grid", x);
+        self.cells[x..].iter().step_by(self.width)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    // Cells row by row along with their coordinates
+    pub fn enumerate(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        let width = self.width;
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, cell)| ((i % width, i / width), cell))
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        assert!(
+            x < self.width && y < self.height,
+            "({}, {}) is off the grid",
+            x,
+            y
+        );
+        &self.cells[y * self.width + x]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        assert!(
+            x < self.width && y < self.height,
+            "({}, {}) is off the grid",
+            x,
+            y
+        );
+        &mut self.cells[y * self.width + x]
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    Empty,
+    Ragged {
+        y: usize,
+        expected: usize,
+        found: usize,
+    },
+    NotADigit {
+        x: usize,
+        y: usize,
+        c: char,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "the grid is empty"),
+            ParseError::Ragged { y, expected, found } => {
+                write!(f, "row {} has {} columns, expected {}", y, found, expected)
+            }
+            ParseError::NotADigit { x, y, c } => {
+                write!(f, "{:?} at ({}, {}) is not a digit", c, x, y)
+            }
+        }
+    }
+}
+
+// One row per line, one digit per cell; blank lines at the end are ignored
+pub fn parse_digits(input: &str) -> Result<Grid<u32>, ParseError> {
+    let mut cells = Vec::new();
+    let mut width = None;
+    let mut height = 0;
+
+    let input = input.trim_end_matches(['\r', '\n']);
+    for (y, line) in input.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let found = line.chars().count();
+        let expected = *width.get_or_insert(found);
+        if found != expected {
+            return Err(ParseError::Ragged { y, expected, found });
+        }
+        for (x, c) in line.chars().enumerate() {
+            cells.push(c.to_digit(10).ok_or(ParseError::NotADigit { x, y, c })?);
+        }
+        height += 1;
+    }
+
+    match width {
+        Some(width) if width > 0 => Ok(Grid {
+            width,
+            height,
+            cells,
+        }),
+        _ => Err(ParseError::Empty),
+    }
+}
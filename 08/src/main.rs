@@ -1,68 +1,276 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use grid::Grid;
+use render::Rgb;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use treehouse::{Blocking, Combine, Rules};
 
-fn is_visible(trees: &Vec<Vec<u32>>, x: usize, y: usize) -> bool {
-    if x == 0 || y == 0 || x == trees.len() - 1 || y == trees.len() - 1 {
-        return true;
+mod bench;
+mod grid;
+mod parallel;
+mod render;
+mod treehouse;
+
+fn rows(trees: &Grid<u32>) -> Vec<Vec<u32>> {
+    (0..trees.height())
+        .map(|y| trees.row(y).copied().collect())
+        .collect()
+}
+
+fn columns(trees: &Grid<u32>) -> Vec<Vec<u32>> {
+    (0..trees.width())
+        .map(|x| trees.column(x).copied().collect())
+        .collect()
+}
+
+// Sweeps the line from both ends keeping the tallest tree seen so far;
+// a tree is visible if it beats that height from either side
+fn visible_line(heights: &[u32]) -> Vec<bool> {
+    let mut visible = vec![false; heights.len()];
+    let mut sweep = |order: &mut dyn Iterator<Item = usize>| {
+        let mut max_height: Option<u32> = None;
+        for i in order {
+            if max_height.is_none_or(|m| heights[i] > m) {
+                visible[i] = true;
+                max_height = Some(heights[i]);
+            }
+        }
+    };
+    sweep(&mut (0..heights.len()));
+    sweep(&mut (0..heights.len()).rev());
+    visible
+}
+
+// Product of the viewing distances towards both ends of the line. The
+// stack keeps the trees not yet blocked by a taller or equal one, so its
+// top after popping is the tree blocking the view.
+fn view_line(heights: &[u32]) -> Vec<usize> {
+    let mut scores = vec![1; heights.len()];
+    let mut look = |order: &mut dyn Iterator<Item = usize>| {
+        let mut stack: Vec<(usize, usize)> = Vec::new();
+        for (pos, i) in order.enumerate() {
+            while let Some((_, top)) = stack.last() {
+                if heights[*top] >= heights[i] {
+                    break;
+                }
+                stack.pop();
+            }
+            let distance = match stack.last() {
+                Some((blocker, _)) => pos - blocker,
+                None => pos,
+            };
+            scores[i] *= distance;
+            stack.push((pos, i));
+        }
+    };
+    look(&mut (0..heights.len()));
+    look(&mut (0..heights.len()).rev());
+    scores
+}
+
+// A tree is visible if it is visible along its row or its column
+fn visibility(trees: &Grid<u32>) -> Grid<bool> {
+    let by_row: Vec<Vec<bool>> = rows(trees).iter().map(|r| visible_line(r)).collect();
+    let by_column: Vec<Vec<bool>> = columns(trees).iter().map(|c| visible_line(c)).collect();
+    Grid::from_fn(trees.width(), trees.height(), |x, y| {
+        by_row[y][x] || by_column[x][y]
+    })
+}
+
+fn scenic_scores(trees: &Grid<u32>) -> Grid<usize> {
+    let by_row: Vec<Vec<usize>> = rows(trees).iter().map(|r| view_line(r)).collect();
+    let by_column: Vec<Vec<usize>> = columns(trees).iter().map(|c| view_line(c)).collect();
+    Grid::from_fn(trees.width(), trees.height(), |x, y| {
+        by_row[y][x] * by_column[x][y]
+    })
+}
+
+// Coordinates of the highest scenic score, the first one in reading order on ties
+fn best_spot(scores: &Grid<usize>) -> Option<(usize, usize)> {
+    scores
+        .enumerate()
+        .fold(
+            None,
+            |best: Option<((usize, usize), usize)>, (pos, score)| match best {
+                Some((_, top)) if top >= *score => best,
+                _ => Some((pos, *score)),
+            },
+        )
+        .map(|(pos, _)| pos)
+}
+
+fn write_image(path: &str, colours: &Grid<Rgb>, scale: usize) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("png") => render::write_png(out, colours, scale),
+        _ => render::write_ppm(&mut out, colours, scale),
     }
+}
 
-    let my_height = trees[y][x];
-    if my_height == 0 {
-        return false;
-    }
-    // println!("Checking {}.{} = {}", x, y, my_height);
-    let mut max_height = 0;
-    // Check row visibility
-    // println!("Check row");
-    for n in 0..trees.len() {
-        // print!("\tEval {}.{} = {}", n, y, trees[y][n]);
-        if n != x {
-            max_height = std::cmp::max(max_height, trees[y][n]);
+fn parse_pos(s: &str) -> Option<(usize, usize)> {
+    let (x, y) = s.split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+fn number_arg(flag: &str, value: Option<String>) -> usize {
+    let value = value.unwrap_or_default();
+    match value.parse() {
+        Ok(n) => n,
+        Err(_) => {
+            eprintln!("{}: invalid number: {:?}", flag, value);
+            std::process::exit(1);
         }
-        if n == x || n == trees.len() - 1 {
-            if max_height < my_height {
-                println!(
-                    "{}.{} -> {}.{}: Yes(r) {} {}",
-                    x, y, n, y, max_height, my_height
-                );
-                return true;
+    }
+}
+
+// Usage: aoc-08 [--bench [size]] [--parallel] [--heatmap visible|scores] [--ansi]
+//               [--image <file.ppm|file.png>] [--scale <n>] [--query <x,y>]... [--top <k>] [--diagonal]
+//               [--max-distance <n>] [--taller] [--combine product|sum|min] [filename]
+fn main() {
+    let mut filename = "input".to_string();
+    let mut heatmap = "scores".to_string();
+    let mut ansi = false;
+    let mut image = None;
+    let mut scale = 4;
+    let mut parallel = false;
+    let mut queries = Vec::new();
+    let mut top = None;
+    let mut rules = Rules::default();
+
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bench" => {
+                let size = args.next().and_then(|a| a.parse().ok()).unwrap_or(4000);
+                bench::run(size);
+                return;
+            }
+            "--parallel" => parallel = true,
+            "--heatmap" => heatmap = args.next().unwrap_or_default(),
+            "--ansi" => ansi = true,
+            "--image" => image = args.next(),
+            "--scale" => {
+                let arg = args.next_if(|a| a.parse::<usize>().is_ok());
+                scale = arg.and_then(|a| a.parse().ok()).unwrap_or(scale);
             }
-            max_height = 0;
-            // println!("");
-            continue;
+            "--query" => {
+                let arg = args.next().unwrap_or_default();
+                match parse_pos(&arg) {
+                    Some(pos) => queries.push(pos),
+                    None => {
+                        eprintln!("Bad coordinate: {}", arg);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--top" => {
+                let arg = args.next_if(|a| a.parse::<usize>().is_ok());
+                top = Some(arg.and_then(|a| a.parse().ok()).unwrap_or(10));
+            }
+            "--diagonal" => rules = rules.diagonals(),
+            "--max-distance" => rules = rules.max_distance(number_arg(&arg, args.next())),
+            "--taller" => rules = rules.blocking(Blocking::Taller),
+            "--combine" => {
+                let arg = args.next().unwrap_or_default();
+                match Combine::parse(&arg) {
+                    Some(c) => rules = rules.combine(c),
+                    None => {
+                        eprintln!("Unknown combinator: {}", arg);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            _ => filename = arg,
         }
-        // println!("");
     }
 
-    //Check column visibility
-    // println!("Check column");
-    max_height = 0;
-    for n in 0..trees.len() {
-        // print!("\tEval {}.{} = {}", n, y, trees[n][x]);
-        if n != y {
-            max_height = std::cmp::max(max_height, trees[n][x]);
+    let input = fs::read_to_string(&filename).unwrap();
+    let trees = match grid::parse_digits(&input) {
+        Ok(trees) => trees,
+        Err(e) => {
+            eprintln!("{}: {}", filename, e);
+            std::process::exit(1);
         }
-        if n == y || n == trees.len() - 1 {
-            if max_height < my_height {
-                // println!(": Yes {} {}", max_height, my_height);
-                println!(
-                    "{}.{} -> {}.{}: Yes(c) {} {}",
-                    x, y, x, n, max_height, my_height
-                );
-                return true;
+    };
+
+    let (visible, scores) = if parallel {
+        (
+            parallel::visibility(&trees),
+            parallel::scenic_scores(&trees),
+        )
+    } else {
+        (visibility(&trees), scenic_scores(&trees))
+    };
+    let best = best_spot(&scores);
+
+    if ansi || image.is_some() {
+        let mut colours = match heatmap.as_str() {
+            "visible" => render::visibility_colours(&visible),
+            "scores" => render::score_colours(&scores),
+            _ => {
+                eprintln!("Unknown heatmap: {}", heatmap);
+                std::process::exit(1);
+            }
+        };
+        if let Some(pos) = best {
+            render::highlight(&mut colours, pos);
+        }
+        if ansi {
+            print!("{}", render::ansi(&colours, &trees));
+        }
+        if let Some(path) = image {
+            if let Err(e) = write_image(&path, &colours, scale.max(1)) {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
             }
-            max_height = 0;
-            // println!("");
-            continue;
         }
-        // println!("");
     }
 
-    false
+    println!("Visible: {}", visible.iter().filter(|v| **v).count());
+
+    let max_score = best.map(|pos| scores[pos]).unwrap_or(0);
+    println!("Max score: {}", max_score);
+    if let Some((x, y)) = best {
+        println!("Best spot: ({}, {})", x, y);
+    }
+
+    for (x, y) in queries {
+        if trees.get(x, y).is_none() {
+            eprintln!("({}, {}) is off the grid", x, y);
+            std::process::exit(1);
+        }
+        print!("{}", treehouse::query(&trees, (x, y), &rules));
+    }
+    if let Some(k) = top {
+        for spot in treehouse::top_k(&trees, &rules, k) {
+            print!("{}", spot);
+        }
+    }
 }
 
+#[cfg(test)]
+fn read_trees(filename: &str) -> Grid<u32> {
+    grid::parse_digits(&fs::read_to_string(filename).unwrap()).unwrap()
+}
+
+#[test]
+fn test_visibility() {
+    let trees = read_trees("input-example");
+    let visible = visibility(&trees);
+    assert_eq!(visible.iter().filter(|v| **v).count(), 21);
+    assert!(visible[(1, 1)] && visible[(2, 1)] && !visible[(3, 1)]);
+    assert!(!visible[(2, 2)] && visible[(3, 2)]);
+
+    let wide = grid::parse_digits("1203\n2011\n3333\n").unwrap();
+    let visible = visibility(&wide);
+    let row: Vec<bool> = visible.row(1).copied().collect();
+    assert_eq!(row, vec![true, false, true, true]);
+}
+
+// The original recursive implementation, kept to check the results against
+#[cfg(test)]
 fn scenic_score_right(
-    trees: &Vec<Vec<u32>>,
+    trees: &[Vec<u32>],
     x: isize,
     y: isize,
     my_height: u32,
@@ -77,8 +285,9 @@ fn scenic_score_right(
     scenic_score_right(trees, x + 1, y, my_height, distance) + 1
 }
 
+#[cfg(test)]
 fn scenic_score_left(
-    trees: &Vec<Vec<u32>>,
+    trees: &[Vec<u32>],
     x: isize,
     y: isize,
     my_height: u32,
@@ -93,8 +302,9 @@ fn scenic_score_left(
     scenic_score_left(trees, x - 1, y, my_height, distance) + 1
 }
 
+#[cfg(test)]
 fn scenic_score_up(
-    trees: &Vec<Vec<u32>>,
+    trees: &[Vec<u32>],
     x: isize,
     y: isize,
     my_height: u32,
@@ -109,8 +319,9 @@ fn scenic_score_up(
     scenic_score_up(trees, x, y - 1, my_height, distance) + 1
 }
 
+#[cfg(test)]
 fn scenic_score_down(
-    trees: &Vec<Vec<u32>>,
+    trees: &[Vec<u32>],
     x: isize,
     y: isize,
     my_height: u32,
@@ -125,7 +336,8 @@ fn scenic_score_down(
     scenic_score_down(trees, x, y + 1, my_height, distance) + 1
 }
 
-fn scenic_score(trees: &Vec<Vec<u32>>, x: usize, y: usize) -> usize {
+#[cfg(test)]
+fn scenic_score(trees: &[Vec<u32>], x: usize, y: usize) -> usize {
     let my_height = trees[y][x];
     let xi = isize::try_from(x).unwrap();
     let yi = isize::try_from(y).unwrap();
@@ -135,46 +347,170 @@ fn scenic_score(trees: &Vec<Vec<u32>>, x: usize, y: usize) -> usize {
     let down = scenic_score_down(trees, xi, yi + 1, my_height, 0);
     left * right * up * down
 }
-fn main() {
-    let filename = "input";
-    let file = File::open(filename).unwrap();
-    let reader = BufReader::new(file);
 
-    let mut trees: Vec<Vec<u32>> = Vec::new();
-
-    for line in reader.lines().flatten() {
-        let mut row = Vec::new();
-        line.chars().for_each(|c| row.push(c.to_digit(10).unwrap()));
-        trees.push(row);
-    }
-
-    let mut visible = 0;
-    for x in 0..trees.len() {
-        for y in 0..trees.len() {
-            if is_visible(&trees, x, y) {
-                visible += 1;
+#[test]
+fn test_scenic_scores() {
+    for filename in ["input-example", "input"] {
+        let trees = read_trees(filename);
+        let scores = scenic_scores(&trees);
+        let rows: Vec<Vec<u32>> = (0..trees.height())
+            .map(|y| trees.row(y).copied().collect())
+            .collect();
+        for y in 0..trees.height() {
+            for x in 0..trees.width() {
+                assert_eq!(scores[(x, y)], scenic_score(&rows, x, y));
             }
         }
     }
 
-    println!("Visible: {}", visible);
+    let scores = scenic_scores(&read_trees("input-example"));
+    assert_eq!(scores[(2, 1)], 4);
+    assert_eq!(scores[(2, 3)], 8);
+    assert_eq!(scores.iter().max(), Some(&8));
+}
+
+#[test]
+fn test_parse_errors() {
+    use grid::ParseError;
+    assert_eq!(
+        grid::parse_digits("123\n45\n"),
+        Err(ParseError::Ragged {
+            y: 1,
+            expected: 3,
+            found: 2
+        })
+    );
+    assert_eq!(
+        grid::parse_digits("123\n4x6\n"),
+        Err(ParseError::NotADigit { x: 1, y: 1, c: 'x' })
+    );
+    assert_eq!(grid::parse_digits(""), Err(ParseError::Empty));
+    assert_eq!(grid::parse_digits("\n\n"), Err(ParseError::Empty));
+    assert_eq!(
+        grid::parse_digits("12\n34\n\n\r\n"),
+        grid::parse_digits("12\n34")
+    );
+    assert_eq!(
+        grid::parse_digits("12\n\n34\n"),
+        Err(ParseError::Ragged {
+            y: 1,
+            expected: 2,
+            found: 0
+        })
+    );
 
-    let mut scores: Vec<Vec<usize>> = Vec::new();
-    for x in 0..trees.len() {
-        let mut row = Vec::new();
-        for y in 0..trees.len() {
-            row.push(scenic_score(&trees, x, y));
-        }
-        scores.push(row);
-    }
-    let mut max_score = 0;
-    for x in 0..trees.len() {
-        // println!();
-        for y in 0..trees.len() {
-            max_score = std::cmp::max(max_score, scores[y][x]);
-            // print!("{}", scores[y][x]);
+    let trees = grid::parse_digits("12\n34\n56\n").unwrap();
+    assert_eq!((trees.width(), trees.height()), (2, 3));
+    assert_eq!(trees.neighbour(0, 0, -1, 0), None);
+    assert_eq!(trees.neighbour(1, 2, 0, 1), None);
+    assert_eq!(trees.neighbour(1, 1, -1, 1), Some((0, 2)));
+    assert_eq!(
+        trees.column(1).copied().collect::<Vec<u32>>(),
+        vec![2, 4, 6]
+    );
+}
+
+#[test]
+fn test_render() {
+    let trees = read_trees("input-example");
+    let scores = scenic_scores(&trees);
+    assert_eq!(best_spot(&scores), Some((2, 3)));
+
+    let mut colours = render::score_colours(&scores);
+    assert_ne!(colours[(2, 1)], colours[(0, 0)]);
+    render::highlight(&mut colours, (2, 3));
+    let ansi = render::ansi(&colours, &trees);
+    assert_eq!(ansi.lines().count(), 5);
+    assert!(ansi.lines().all(|l| l.ends_with("\x1b[0m")));
+
+    let mut ppm = Vec::new();
+    render::write_ppm(
+        &mut ppm,
+        &render::visibility_colours(&visibility(&trees)),
+        2,
+    )
+    .unwrap();
+    let header = b"P6\n10 10\n255\n";
+    assert!(ppm.starts_with(header));
+    assert_eq!(ppm.len(), header.len() + 10 * 10 * 3);
+    // The centre tree is hidden and the pixels of its block match
+    let pixel = |x: usize, y: usize| {
+        let i = header.len() + (y * 10 + x) * 3;
+        ppm[i..i + 3].to_vec()
+    };
+    assert_eq!(pixel(4, 4), pixel(5, 5));
+    assert_ne!(pixel(4, 4), pixel(0, 0));
+}
+
+#[test]
+fn test_treehouse() {
+    use treehouse::{top_k, View};
+    let trees = read_trees("input-example");
+
+    let spot = treehouse::query(&trees, (2, 3), &Rules::default());
+    assert_eq!(spot.score, 8);
+    assert_eq!(
+        spot.views[0],
+        View {
+            direction: (0, -1),
+            distance: 2,
+            blocker: Some((2, 1))
         }
+    );
+    assert_eq!(spot.views[2].blocker, None);
+
+    // The default rules agree with the puzzle scores everywhere
+    let scores = scenic_scores(&read_trees("input"));
+    let ranked = top_k(&read_trees("input"), &Rules::default(), 3);
+    assert_eq!(ranked[0].score, *scores.iter().max().unwrap());
+    assert_eq!(Some(ranked[0].pos), best_spot(&scores));
+    assert!(ranked.windows(2).all(|w| w[0].score >= w[1].score));
+
+    let rules = Rules::default().max_distance(1).combine(Combine::Sum);
+    assert_eq!(treehouse::query(&trees, (2, 3), &rules).score, 4);
+    let rules = Rules::default().combine(Combine::Min);
+    assert_eq!(treehouse::query(&trees, (2, 3), &rules).score, 1);
+    assert_eq!(treehouse::query(&trees, (0, 0), &rules).score, 0);
+
+    // Up-left from (2, 3) the 5 at (1, 2) blocks, up-right sees over to the edge
+    let rules = Rules::default().diagonals();
+    let spot = treehouse::query(&trees, (2, 3), &rules);
+    assert_eq!(spot.views.len(), 8);
+    assert_eq!(spot.views[4].distance, 1);
+    assert_eq!(spot.views[4].blocker, Some((1, 2)));
+    assert_eq!(spot.views[5].distance, 2);
+    assert_eq!(spot.views[5].blocker, None);
+
+    // Looking left from (2, 1) the 5 next to it no longer blocks
+    let rules = Rules::default().blocking(Blocking::Taller);
+    assert_eq!(
+        treehouse::query(&trees, (2, 1), &rules).views[1].distance,
+        2
+    );
+}
+
+#[test]
+fn test_parallel() {
+    let mut forests = vec![read_trees("input-example"), read_trees("input")];
+    forests.push(bench::generate(257));
+    forests.push(grid::parse_digits("1203\n2011\n3333\n").unwrap());
+    forests.push(grid::parse_digits("7\n").unwrap());
+    for trees in forests {
+        assert_eq!(parallel::visibility(&trees), visibility(&trees));
+        assert_eq!(parallel::scenic_scores(&trees), scenic_scores(&trees));
     }
-    println!();
-    println!("Max score: {}", max_score);
+}
+
+#[test]
+#[should_panic(expected = "column 2 is off the grid")]
+fn test_column_bounds() {
+    let trees = grid::parse_digits("12\n34\n56\n").unwrap();
+    trees.column(2).count();
+}
+
+#[test]
+#[should_panic(expected = "row 3 is off the grid")]
+fn test_row_bounds() {
+    let trees = grid::parse_digits("12\n34\n56\n").unwrap();
+    trees.row(3).count();
 }
@@ -0,0 +1,38 @@
+use crate::grid::Grid;
+use crate::{view_line, visible_line};
+use rayon::prelude::*;
+
+// Every row and every column is independent, so each is copied out and
+// handed to a worker running the same per-line kernel as the sequential
+// path. Results are merged cell by cell in a fixed order, so only the
+// scheduling differs between runs.
+
+fn rows(trees: &Grid<u32>) -> Vec<Vec<u32>> {
+    (0..trees.height())
+        .into_par_iter()
+        .map(|y| trees.row(y).copied().collect())
+        .collect()
+}
+
+fn columns(trees: &Grid<u32>) -> Vec<Vec<u32>> {
+    (0..trees.width())
+        .into_par_iter()
+        .map(|x| trees.column(x).copied().collect())
+        .collect()
+}
+
+pub fn visibility(trees: &Grid<u32>) -> Grid<bool> {
+    let by_row: Vec<Vec<bool>> = rows(trees).par_iter().map(|r| visible_line(r)).collect();
+    let by_column: Vec<Vec<bool>> = columns(trees).par_iter().map(|c| visible_line(c)).collect();
+    Grid::from_fn(trees.width(), trees.height(), |x, y| {
+        by_row[y][x] || by_column[x][y]
+    })
+}
+
+pub fn scenic_scores(trees: &Grid<u32>) -> Grid<usize> {
+    let by_row: Vec<Vec<usize>> = rows(trees).par_iter().map(|r| view_line(r)).collect();
+    let by_column: Vec<Vec<usize>> = columns(trees).par_iter().map(|c| view_line(c)).collect();
+    Grid::from_fn(trees.width(), trees.height(), |x, y| {
+        by_row[y][x] * by_column[x][y]
+    })
+}
@@ -0,0 +1,89 @@
+use crate::grid::Grid;
+use std::io::{self, Write};
+
+pub type Rgb = [u8; 3];
+
+const HIDDEN: Rgb = [40, 40, 40];
+const VISIBLE: Rgb = [60, 170, 80];
+const HIGHLIGHT: Rgb = [230, 40, 200];
+
+// Dark blue through green to yellow as `t` goes from 0 to 1
+fn heat(t: f64) -> Rgb {
+    let stops: [Rgb; 4] = [[20, 20, 70], [30, 120, 160], [90, 190, 80], [250, 230, 60]];
+    let t = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let i = (t as usize).min(stops.len() - 2);
+    let f = t - i as f64;
+    let mut rgb = [0; 3];
+    for c in 0..3 {
+        let (a, b) = (stops[i][c] as f64, stops[i + 1][c] as f64);
+        rgb[c] = (a + (b - a) * f).round() as u8;
+    }
+    rgb
+}
+
+pub fn visibility_colours(visible: &Grid<bool>) -> Grid<Rgb> {
+    visible.map(|v| if *v { VISIBLE } else { HIDDEN })
+}
+
+// Scores span several orders of magnitude, so the scale is logarithmic
+pub fn score_colours(scores: &Grid<usize>) -> Grid<Rgb> {
+    let max = scores.iter().max().copied().unwrap_or(0);
+    let top = (max as f64).ln_1p();
+    scores.map(|s| {
+        if top == 0.0 {
+            heat(0.0)
+        } else {
+            heat((*s as f64).ln_1p() / top)
+        }
+    })
+}
+
+pub fn highlight(colours: &mut Grid<Rgb>, pos: (usize, usize)) {
+    colours[pos] = HIGHLIGHT;
+}
+
+// Tree heights drawn over 24-bit background colours
+pub fn ansi(colours: &Grid<Rgb>, trees: &Grid<u32>) -> String {
+    let mut out = String::new();
+    for y in 0..colours.height() {
+        for (x, [r, g, b]) in colours.row(y).enumerate() {
+            out += &format!("\x1b[48;2;{};{};{}m{}", r, g, b, trees[(x, y)]);
+        }
+        out += "\x1b[0m\n";
+    }
+    out
+}
+
+// Each cell becomes a `scale` x `scale` block of pixels
+fn pixels(colours: &Grid<Rgb>, scale: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(colours.width() * colours.height() * scale * scale * 3);
+    for y in 0..colours.height() {
+        let mut line = Vec::with_capacity(colours.width() * scale * 3);
+        for rgb in colours.row(y) {
+            for _ in 0..scale {
+                line.extend_from_slice(rgb);
+            }
+        }
+        for _ in 0..scale {
+            data.extend_from_slice(&line);
+        }
+    }
+    data
+}
+
+pub fn write_ppm<W: Write>(out: &mut W, colours: &Grid<Rgb>, scale: usize) -> io::Result<()> {
+    let (width, height) = (colours.width() * scale, colours.height() * scale);
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    out.write_all(&pixels(colours, scale))
+}
+
+pub fn write_png<W: Write>(out: W, colours: &Grid<Rgb>, scale: usize) -> io::Result<()> {
+    let (width, height) = (colours.width() * scale, colours.height() * scale);
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels(colours, scale))?;
+    writer.finish()?;
+    Ok(())
+}
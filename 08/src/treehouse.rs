@@ -0,0 +1,177 @@
+use crate::grid::Grid;
+use std::fmt;
+
+pub const STRAIGHT: [(isize, isize); 4] = [(0, -1), (-1, 0), (0, 1), (1, 0)];
+pub const DIAGONAL: [(isize, isize); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+pub fn direction_name((dx, dy): (isize, isize)) -> &'static str {
+    match (dx, dy) {
+        (0, -1) => "up",
+        (-1, 0) => "left",
+        (0, 1) => "down",
+        (1, 0) => "right",
+        (-1, -1) => "up-left",
+        (1, -1) => "up-right",
+        (-1, 1) => "down-left",
+        (1, 1) => "down-right",
+        _ => "?",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Combine {
+    Product,
+    Sum,
+    Min,
+}
+
+impl Combine {
+    pub fn parse(s: &str) -> Option<Combine> {
+        match s {
+            "product" => Some(Combine::Product),
+            "sum" => Some(Combine::Sum),
+            "min" => Some(Combine::Min),
+            _ => None,
+        }
+    }
+
+    fn apply(self, distances: impl Iterator<Item = usize>) -> usize {
+        match self {
+            Combine::Product => distances.product(),
+            Combine::Sum => distances.sum(),
+            Combine::Min => distances.min().unwrap_or(0),
+        }
+    }
+}
+
+// Which trees stop the view: the puzzle's rule is any tree at least as tall
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Blocking {
+    AtLeast,
+    Taller,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rules {
+    directions: Vec<(isize, isize)>,
+    max_distance: Option<usize>,
+    blocking: Blocking,
+    combine: Combine,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            directions: STRAIGHT.to_vec(),
+            max_distance: None,
+            blocking: Blocking::AtLeast,
+            combine: Combine::Product,
+        }
+    }
+}
+
+impl Rules {
+    pub fn diagonals(mut self) -> Rules {
+        self.directions.extend(DIAGONAL);
+        self
+    }
+
+    pub fn max_distance(mut self, distance: usize) -> Rules {
+        self.max_distance = Some(distance);
+        self
+    }
+
+    pub fn blocking(mut self, blocking: Blocking) -> Rules {
+        self.blocking = blocking;
+        self
+    }
+
+    pub fn combine(mut self, combine: Combine) -> Rules {
+        self.combine = combine;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct View {
+    pub direction: (isize, isize),
+    pub distance: usize,
+    // None when the view reaches the edge or the distance limit
+    pub blocker: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Treehouse {
+    pub pos: (usize, usize),
+    pub views: Vec<View>,
+    pub score: usize,
+}
+
+impl fmt::Display for Treehouse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "({}, {}) score {}", self.pos.0, self.pos.1, self.score)?;
+        for view in &self.views {
+            write!(
+                f,
+                "\t{:<10} {}",
+                direction_name(view.direction),
+                view.distance
+            )?;
+            if let Some((x, y)) = view.blocker {
+                write!(f, "\tblocked by ({}, {})", x, y)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn look(trees: &Grid<u32>, pos: (usize, usize), direction: (isize, isize), rules: &Rules) -> View {
+    let height = trees[pos];
+    let limit = rules.max_distance.unwrap_or(usize::MAX);
+    let mut distance = 0;
+    for cell in trees.walk(pos, direction).skip(1).take(limit) {
+        distance += 1;
+        let blocked = match rules.blocking {
+            Blocking::AtLeast => trees[cell] >= height,
+            Blocking::Taller => trees[cell] > height,
+        };
+        if blocked {
+            return View {
+                direction,
+                distance,
+                blocker: Some(cell),
+            };
+        }
+    }
+    View {
+        direction,
+        distance,
+        blocker: None,
+    }
+}
+
+pub fn query(trees: &Grid<u32>, pos: (usize, usize), rules: &Rules) -> Treehouse {
+    let views: Vec<View> = rules
+        .directions
+        .iter()
+        .map(|d| look(trees, pos, *d, rules))
+        .collect();
+    let score = rules.combine.apply(views.iter().map(|v| v.distance));
+    Treehouse { pos, views, score }
+}
+
+// The `k` best locations, highest score first and reading order on ties
+pub fn top_k(trees: &Grid<u32>, rules: &Rules, k: usize) -> Vec<Treehouse> {
+    let mut ranked: Vec<Treehouse> = trees
+        .enumerate()
+        .map(|(pos, _)| query(trees, pos, rules))
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then((a.pos.1, a.pos.0).cmp(&(b.pos.1, b.pos.0)))
+    });
+    ranked.truncate(k);
+    ranked
+}
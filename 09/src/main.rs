@@ -1,11 +1,52 @@
-use std::collections::{HashSet, VecDeque};
-use std::io::BufRead;
+use render::Bounds;
+use std::collections::HashSet;
+use std::fmt;
 
+mod motion;
+mod render;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Move {
     Up(usize),
     Down(usize),
     Left(usize),
     Right(usize),
+    UpLeft(usize),
+    UpRight(usize),
+    DownLeft(usize),
+    DownRight(usize),
+}
+
+impl Move {
+    // Head displacement of a single step, and the number of steps
+    fn step(&self) -> ((isize, isize), usize) {
+        match *self {
+            Move::Up(s) => ((0, 1), s),
+            Move::Down(s) => ((0, -1), s),
+            Move::Left(s) => ((-1, 0), s),
+            Move::Right(s) => ((1, 0), s),
+            Move::UpLeft(s) => ((-1, 1), s),
+            Move::UpRight(s) => ((1, 1), s),
+            Move::DownLeft(s) => ((-1, -1), s),
+            Move::DownRight(s) => ((1, -1), s),
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dir = match self {
+            Move::Up(_) => "U",
+            Move::Down(_) => "D",
+            Move::Left(_) => "L",
+            Move::Right(_) => "R",
+            Move::UpLeft(_) => "UL",
+            Move::UpRight(_) => "UR",
+            Move::DownLeft(_) => "DL",
+            Move::DownRight(_) => "DR",
+        };
+        write!(f, "{} {}", dir, self.step().1)
+    }
 }
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 struct Point {
@@ -68,22 +109,11 @@ impl Rope {
     }
 
     fn do_move(&mut self, move_: Move) {
-        let mut expanded = VecDeque::new();
-        match move_ {
-            Move::Up(s) => (0..s).for_each(|_x| expanded.push_back(Move::Up(0))),
-            Move::Down(s) => (0..s).for_each(|_x| expanded.push_back(Move::Down(0))),
-            Move::Left(s) => (0..s).for_each(|_x| expanded.push_back(Move::Left(0))),
-            Move::Right(s) => (0..s).for_each(|_x| expanded.push_back(Move::Right(0))),
-        }
-
-        expanded.iter().for_each(|m| {
+        let ((dx, dy), steps) = move_.step();
+        (0..steps).for_each(|_x| {
             //Move Head
-            match m {
-                Move::Up(_) => self.head.y += 1,
-                Move::Down(_) => self.head.y -= 1,
-                Move::Left(_) => self.head.x -= 1,
-                Move::Right(_) => self.head.x += 1,
-            }
+            self.head.x += dx;
+            self.head.y += dy;
             let mut prev_knot_pos = self.head;
             self.knots.iter_mut().for_each(|knot| {
                 let abs = prev_knot_pos.abs_distance(&knot.pos);
@@ -94,38 +124,82 @@ impl Rope {
                 prev_knot_pos = knot.pos;
             })
         });
-        println!();
     }
 }
 
-fn trace_rope(rope: &mut Rope, lines: &[String]) -> usize {
-    for line in lines.iter() {
-        let mut split = line.split_ascii_whitespace();
-        let dir = split.next().unwrap();
-        let steps = split.next().unwrap().parse::<usize>().unwrap();
-        let move_ = match dir {
-            "U" => Move::Up(steps),
-            "D" => Move::Down(steps),
-            "L" => Move::Left(steps),
-            "R" => Move::Right(steps),
-            _ => Move::Up(0),
-        };
-        rope.do_move(move_);
+fn trace_rope(rope: &mut Rope, moves: &[Move]) -> usize {
+    for move_ in moves {
+        rope.do_move(*move_);
     }
     rope.knots.last().unwrap().places.len()
 }
 
+// Area covering every position the rope reaches between motions
+fn trace_bounds(knots: usize, moves: &[Move]) -> Bounds {
+    let mut rope = Rope::new(knots);
+    let mut bounds = Bounds::around(&rope);
+    for move_ in moves {
+        rope.do_move(*move_);
+        bounds.merge(&Bounds::around(&rope));
+    }
+    bounds
+}
+
+// Prints the rope after every motion, as in the puzzle walkthrough
+fn draw_steps(knots: usize, moves: &[Move]) {
+    let bounds = trace_bounds(knots, moves);
+    let mut rope = Rope::new(knots);
+    println!("== Initial State ==\n");
+    println!("{}", render::draw(&rope, &bounds));
+    for move_ in moves {
+        rope.do_move(*move_);
+        println!("== {} ==\n", move_);
+        println!("{}", render::draw(&rope, &bounds));
+    }
+}
+
+// Usage: aoc-09 [--draw [knots]] [--step] [filename]
 fn main() {
-    let filename = "input";
-    let file = std::fs::File::open(filename).unwrap();
-    let reader = std::io::BufReader::new(file);
-    let lines: Vec<String> = reader.lines().flatten().collect();
+    let mut filename = "input".to_string();
+    let mut draw = None;
+    let mut step = false;
+
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--draw" => {
+                let knots = args.next_if(|a| a.parse::<usize>().is_ok());
+                draw = Some(knots.and_then(|a| a.parse().ok()).unwrap_or(9));
+            }
+            "--step" => step = true,
+            _ => filename = arg,
+        }
+    }
+
+    let input = std::fs::read_to_string(&filename).unwrap();
+    let moves = match motion::parse_motions(&input) {
+        Ok(moves) => moves,
+        Err(e) => {
+            eprintln!("{}: {}", filename, e);
+            std::process::exit(1);
+        }
+    };
 
     let mut rope = Rope::new(1);
-    let visited = trace_rope(&mut rope, &lines);
+    let visited = trace_rope(&mut rope, &moves);
 
     let mut rope = Rope::new(9);
-    let visited2 = trace_rope(&mut rope, &lines);
+    let visited2 = trace_rope(&mut rope, &moves);
+
+    if let Some(knots) = draw {
+        if step {
+            draw_steps(knots, &moves);
+        } else {
+            let mut rope = Rope::new(knots);
+            trace_rope(&mut rope, &moves);
+            println!("{}", render::draw(&rope, &Bounds::around(&rope)));
+        }
+    }
 
     println!("Visited {}", visited);
     println!("Visited {}", visited2);
@@ -184,3 +258,83 @@ fn test_example2() {
     assert_eq!(rope.knots.last().unwrap().pos.y, 6);
     assert_eq!(rope.knots.last().unwrap().places.len(), 36);
 }
+
+#[test]
+fn test_parse_motions() {
+    use motion::{parse_motions, ParseError};
+    let input = "# larger example\nR 5\nu 8   # lowercase\n\nUL 3\ndr 2\n";
+    assert_eq!(
+        parse_motions(input),
+        Ok(vec![
+            Move::Right(5),
+            Move::Up(8),
+            Move::UpLeft(3),
+            Move::DownRight(2)
+        ])
+    );
+
+    assert_eq!(
+        parse_motions("R 1\nX 2\n"),
+        Err(ParseError::UnknownDirection {
+            line: 2,
+            found: "X".to_string()
+        })
+    );
+    assert_eq!(
+        parse_motions("R\n"),
+        Err(ParseError::MissingSteps { line: 1 })
+    );
+    assert_eq!(
+        parse_motions("\n\nL -3\n"),
+        Err(ParseError::BadSteps {
+            line: 3,
+            found: "-3".to_string()
+        })
+    );
+    assert_eq!(
+        parse_motions("D 1 2\n").unwrap_err().to_string(),
+        "line 1: unexpected \"2\" after the step count"
+    );
+
+    // A diagonal step drags the tail diagonally behind the head
+    let mut rope = Rope::new(1);
+    assert_eq!(trace_rope(&mut rope, &parse_motions("UR 3\n").unwrap()), 3);
+    assert_eq!((rope.head.x, rope.head.y), (3, 3));
+    assert_eq!(rope.knots[0].pos, Point { x: 2, y: 2 });
+}
+
+#[test]
+fn test_draw() {
+    let moves = motion::parse_motions("R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2\n").unwrap();
+
+    let mut rope = Rope::new(1);
+    rope.do_move(moves[0]);
+    let bounds = Bounds {
+        min: Point::new(),
+        max: Point { x: 5, y: 4 },
+    };
+    assert_eq!(
+        render::draw(&rope, &bounds),
+        "......\n......\n......\n......\ns##TH.\n"
+    );
+
+    // The tail trail from the puzzle, sized to what the rope visited
+    let mut rope = Rope::new(1);
+    trace_rope(&mut rope, &moves);
+    let bounds = Bounds::around(&rope);
+    assert_eq!(bounds.max, Point { x: 4, y: 4 });
+    let trail: String = render::draw(&rope, &bounds)
+        .chars()
+        .map(|c| if c == 'H' || c == 'T' { '#' } else { c })
+        .collect();
+    assert_eq!(trail, "..##.\n...##\n.####\n....#\ns###.\n");
+
+    let mut rope = Rope::new(9);
+    rope.do_move(Move::Right(4));
+    rope.do_move(Move::Up(4));
+    assert_eq!(
+        render::draw(&rope, &Bounds::around(&rope)),
+        "....H\n....1\n..432\n.5...\n6....\n"
+    );
+    assert_eq!(trace_bounds(9, &moves).min, Point::new());
+}
@@ -0,0 +1,82 @@
+use crate::Move;
+use std::fmt;
+
+// Line numbers start at 1, as an editor shows them
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    UnknownDirection { line: usize, found: String },
+    MissingSteps { line: usize },
+    BadSteps { line: usize, found: String },
+    TrailingInput { line: usize, found: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnknownDirection { line, found } => {
+                write!(f, "line {}: unknown direction {:?}", line, found)
+            }
+            ParseError::MissingSteps { line } => write!(f, "line {}: missing step count", line),
+            ParseError::BadSteps { line, found } => {
+                write!(f, "line {}: {:?} is not a step count", line, found)
+            }
+            ParseError::TrailingInput { line, found } => {
+                write!(
+                    f,
+                    "line {}: unexpected {:?} after the step count",
+                    line, found
+                )
+            }
+        }
+    }
+}
+
+fn direction(s: &str, steps: usize) -> Option<Move> {
+    let move_ = match s.to_ascii_uppercase().as_str() {
+        "U" => Move::Up(steps),
+        "D" => Move::Down(steps),
+        "L" => Move::Left(steps),
+        "R" => Move::Right(steps),
+        "UL" | "LU" => Move::UpLeft(steps),
+        "UR" | "RU" => Move::UpRight(steps),
+        "DL" | "LD" => Move::DownLeft(steps),
+        "DR" | "RD" => Move::DownRight(steps),
+        _ => return None,
+    };
+    Some(move_)
+}
+
+// One `<direction> <steps>` per line; blank lines and `#` comments are skipped
+pub fn parse_motions(input: &str) -> Result<Vec<Move>, ParseError> {
+    let mut moves = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line_no = i + 1;
+        let line = match line.split_once('#') {
+            Some((content, _comment)) => content,
+            None => line,
+        };
+        let mut split = line.split_ascii_whitespace();
+        let Some(dir) = split.next() else {
+            continue;
+        };
+        let steps = match split.next() {
+            Some(s) => s.parse::<usize>().map_err(|_| ParseError::BadSteps {
+                line: line_no,
+                found: s.to_string(),
+            })?,
+            None => return Err(ParseError::MissingSteps { line: line_no }),
+        };
+        if let Some(extra) = split.next() {
+            return Err(ParseError::TrailingInput {
+                line: line_no,
+                found: extra.to_string(),
+            });
+        }
+        let move_ = direction(dir, steps).ok_or(ParseError::UnknownDirection {
+            line: line_no,
+            found: dir.to_string(),
+        })?;
+        moves.push(move_);
+    }
+    Ok(moves)
+}
@@ -0,0 +1,73 @@
+use crate::{Point, Rope};
+
+// Inclusive corners of the drawn area
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Bounds {
+    fn new(p: Point) -> Bounds {
+        Bounds { min: p, max: p }
+    }
+
+    fn include(&mut self, p: &Point) {
+        self.min.x = self.min.x.min(p.x);
+        self.min.y = self.min.y.min(p.y);
+        self.max.x = self.max.x.max(p.x);
+        self.max.y = self.max.y.max(p.y);
+    }
+
+    // Everything the rope has touched so far, plus the start
+    pub fn around(rope: &Rope) -> Bounds {
+        let mut bounds = Bounds::new(Point::new());
+        bounds.include(&rope.head);
+        for knot in &rope.knots {
+            bounds.include(&knot.pos);
+            knot.places.iter().for_each(|p| bounds.include(p));
+        }
+        bounds
+    }
+
+    pub fn merge(&mut self, other: &Bounds) {
+        self.include(&other.min);
+        self.include(&other.max);
+    }
+}
+
+fn knot_symbol(rope: &Rope, index: usize) -> char {
+    if rope.knots.len() == 1 {
+        'T'
+    } else {
+        char::from_digit(index as u32 + 1, 36).unwrap_or('*')
+    }
+}
+
+// Draws like the puzzle, with y growing upwards. The head wins over the
+// knots, a knot over any knot behind it, then the start `s` and the tail
+// trail `#`.
+pub fn draw(rope: &Rope, bounds: &Bounds) -> String {
+    let trail = rope.knots.last().map(|k| &k.places);
+    let start = Point::new();
+    let mut out = String::new();
+    for y in (bounds.min.y..=bounds.max.y).rev() {
+        for x in bounds.min.x..=bounds.max.x {
+            let p = Point { x, y };
+            let c = if p == rope.head {
+                'H'
+            } else if let Some(i) = rope.knots.iter().position(|k| k.pos == p) {
+                knot_symbol(rope, i)
+            } else if p == start {
+                's'
+            } else if trail.is_some_and(|t| t.contains(&p)) {
+                '#'
+            } else {
+                '.'
+            };
+            out.push(c);
+        }
+        out.push('\n');
+    }
+    out
+}